@@ -230,6 +230,146 @@ pub unsafe fn bx_ns(addr: u32) {
     asm!("bxns {}", in(reg) addr, options(nomem, nostack, preserves_flags));
 }
 
+/// Types that can be accessed with the exclusive access instructions: `u8`, `u16` and `u32`.
+///
+/// Not available on ARMv6-M, which has no exclusive access instructions.
+#[cfg(all(cortex_m, not(armv6m)))]
+pub trait Exclusive: Copy + exclusive::Sealed {
+    /// Loads the value at `addr` and marks the address for exclusive access.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be valid for reads and aligned.
+    unsafe fn load_exclusive(addr: *const Self) -> Self;
+
+    /// Stores the value at `addr` if the address is still marked for exclusive access.
+    /// Returns `true` if the value was stored.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be valid for writes and aligned.
+    unsafe fn store_exclusive(self, addr: *mut Self) -> bool;
+}
+
+#[cfg(all(cortex_m, not(armv6m)))]
+mod exclusive {
+    use core::arch::asm;
+
+    pub trait Sealed {}
+
+    macro_rules! exclusive {
+        ($T:ty, $ldrex:literal, $strex:literal) => {
+            impl Sealed for $T {}
+
+            impl super::Exclusive for $T {
+                #[inline(always)]
+                unsafe fn load_exclusive(addr: *const Self) -> Self {
+                    let value: $T;
+                    asm!(
+                        concat!($ldrex, " {value}, [{addr}]"),
+                        value = out(reg) value,
+                        addr = in(reg) addr,
+                        options(nostack, preserves_flags, readonly),
+                    );
+                    value
+                }
+
+                #[inline(always)]
+                unsafe fn store_exclusive(self, addr: *mut Self) -> bool {
+                    let failed: u32;
+                    asm!(
+                        concat!($strex, " {failed}, {value}, [{addr}]"),
+                        failed = out(reg) failed,
+                        value = in(reg) self,
+                        addr = in(reg) addr,
+                        options(nostack, preserves_flags),
+                    );
+                    failed == 0
+                }
+            }
+        };
+    }
+
+    exclusive!(u8, "ldrexb", "strexb");
+    exclusive!(u16, "ldrexh", "strexh");
+    exclusive!(u32, "ldrex", "strex");
+}
+
+/// Load Exclusive
+///
+/// Loads the value at `addr` and marks the address for exclusive access by the next
+/// [`strex`]. Uses LDREXB, LDREXH or LDREX, depending on the width of `T`.
+///
+/// The exclusive access instructions don't order memory accesses, use [`dmb`] for that.
+///
+/// # Safety
+///
+/// `addr` must be valid for reads and aligned.
+#[cfg(all(cortex_m, not(armv6m)))]
+#[inline(always)]
+pub unsafe fn ldrex<T: Exclusive>(addr: *const T) -> T {
+    T::load_exclusive(addr)
+}
+
+/// Store Exclusive
+///
+/// Stores `value` at `addr` if the address is still marked for exclusive access by the
+/// preceding [`ldrex`]. Returns `true` if the value was stored. Uses STREXB, STREXH or STREX,
+/// depending on the width of `T`.
+///
+/// # Safety
+///
+/// `addr` must be valid for writes and aligned.
+#[cfg(all(cortex_m, not(armv6m)))]
+#[inline(always)]
+pub unsafe fn strex<T: Exclusive>(addr: *mut T, value: T) -> bool {
+    value.store_exclusive(addr)
+}
+
+/// Clear Exclusive
+///
+/// Clears the local exclusive monitor, so the next [`strex`] fails unless it is preceded by
+/// an [`ldrex`].
+#[cfg(all(cortex_m, not(armv6m)))]
+#[inline(always)]
+pub fn clrex() {
+    unsafe { asm!("clrex", options(nomem, nostack, preserves_flags)) };
+}
+
+/// Updates the value at `addr` atomically with an LDREX/STREX loop.
+///
+/// `f` is called with the current value until the new value it returns is stored exclusively,
+/// and may therefore be called more than once. If `f` returns `None`, the update is abandoned.
+/// Returns the previous value, as `Ok` if the new value was stored.
+///
+/// The update doesn't order other memory accesses, use [`dmb`] for that.
+///
+/// # Safety
+///
+/// `addr` must be valid for reads and writes and aligned.
+#[cfg(all(cortex_m, not(armv6m)))]
+#[inline]
+pub unsafe fn exclusive_update<T, F>(addr: *mut T, mut f: F) -> Result<T, T>
+where
+    T: Exclusive,
+    F: FnMut(T) -> Option<T>,
+{
+    loop {
+        let old = ldrex(addr);
+        match f(old) {
+            Some(new) => {
+                if strex(addr, new) {
+                    return Ok(old);
+                }
+            }
+            None => {
+                clrex();
+                return Err(old);
+            }
+        }
+    }
+}
+
 /// Semihosting syscall.
 ///
 /// This method is used by cortex-m-semihosting to provide semihosting syscalls.
@@ -1,167 +1,491 @@
 //! Atomic abstractions for single and multi-core systems.
+//!
+//! With the `atomic-cas` feature enabled, or natively, the atomic types of `core` are re-exported.
+//! Otherwise, the types are emulated: with exclusive access (LDREX/STREX) loops where the core
+//! has these instructions, and with critical sections on ARMv6-M. The emulated types have the same
+//! API as the types of `core`, but are only atomic on single-core systems.
 
 #[cfg(all(feature = "multi-core", not(feature = "atomic-cas")))]
 compile_error!(
     "The `multi-core` feature requires atomic-cas operations to be available on the target. Enable the `atomic-cas` feature."
 );
 
-#[cfg(all(not(feature = "atomic-cas"), not(target_has_atomic = "8")))]
+#[cfg(all(feature = "atomic-cas", not(target_has_atomic = "8")))]
 compile_error!(
     "The `atomic-cas` feature requires the target to have atomic operations on at least 8-bit integers."
 );
 
-// ----------------------------AtomicU8----------------------------
-
 pub use core::sync::atomic::Ordering;
 
+#[cfg(not(all(not(feature = "atomic-cas"), cortex_m)))]
+pub use core::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicPtr, AtomicU16, AtomicU32, AtomicU8, AtomicUsize,
+};
+
 #[cfg(all(not(feature = "atomic-cas"), cortex_m))]
-use core::cell::UnsafeCell;
+pub use emulated::{AtomicBool, AtomicI32, AtomicPtr, AtomicU16, AtomicU32, AtomicU8, AtomicUsize};
 
 #[cfg(all(not(feature = "atomic-cas"), cortex_m))]
-/// An atomic `u8`.
-pub struct AtomicU8 {
-    value: UnsafeCell<u8>,
-}
+mod emulated {
+    use super::Ordering;
+    #[cfg(not(armv6m))]
+    use crate::asm::{self, Exclusive};
+    #[cfg(armv6m)]
+    use crate::interrupt::{self, Mutex};
+    #[cfg(armv6m)]
+    use core::cell::Cell;
+    use core::fmt;
+    #[cfg(not(armv6m))]
+    use core::{cell::UnsafeCell, sync::atomic::compiler_fence};
 
-#[cfg(not(all(not(feature = "atomic-cas"), cortex_m)))]
-pub use core::sync::atomic::AtomicU8;
+    /// The value types of the emulated atomics.
+    ///
+    /// Where the core has the exclusive access instructions, a value is accessed as the integer
+    /// of the same size.
+    #[cfg(not(armv6m))]
+    trait Primitive: Copy {
+        type Bits: Exclusive;
 
-#[cfg(all(not(feature = "atomic-cas"), cortex_m))]
-impl AtomicU8 {
-    /// Creates a new atomic u8.
-    pub const fn new(value: u8) -> Self {
-        Self {
-            value: UnsafeCell::new(value),
-        }
-    }
-
-    /// Loads the value.
-    pub fn load(&self, _: Ordering) -> u8 {
-        use crate::interrupt;
-        interrupt::free(|| unsafe { *self.value.get() })
-    }
-
-    /// Stores a value.
-    pub fn store(&self, value: u8, _: Ordering) {
-        use crate::interrupt;
-        interrupt::free(|| unsafe {
-            *self.value.get() = value;
-        });
-    }
-
-    /// Compares the value and exchanges it.
-    pub fn compare_exchange(
-        &self,
-        current: u8,
-        new: u8,
-        _: Ordering,
-        _: Ordering,
-    ) -> Result<u8, u8> {
-        use crate::interrupt;
-        interrupt::free(|| {
-            // Safety:
-            // 1. This is safe because we are on a single-core system, in an interrupt-free context.
-            // 2. No reference to the value can be acquired outside of this type.
-            let value: u8 = unsafe { *self.value.get() };
-
-            if value == current {
-                // Safety:
-                // 1. This is safe because we are on a single-core system, in an interrupt-free context.
-                // 2. No reference to the value can be acquired outside of this type.
-                // 3. The local `value` has been copied and not referenced.
-                unsafe {
-                    *self.value.get() = new;
-                }
-                Ok(value)
-            } else {
-                Err(value)
+        fn to_bits(self) -> Self::Bits;
+
+        fn from_bits(bits: Self::Bits) -> Self;
+    }
+
+    /// The value types of the emulated atomics.
+    #[cfg(armv6m)]
+    trait Primitive: Copy {}
+
+    #[cfg(armv6m)]
+    impl<T: Copy> Primitive for T {}
+
+    /// Implements `Primitive` for the types that convert to their bits with `as`.
+    #[cfg(not(armv6m))]
+    macro_rules! primitive {
+        ($($T:ty => $Bits:ty),*) => {
+            $(
+                impl Primitive for $T {
+                    type Bits = $Bits;
+
+                    #[inline(always)]
+                    fn to_bits(self) -> $Bits {
+                        self as $Bits
+                    }
+
+                    #[inline(always)]
+                    fn from_bits(bits: $Bits) -> Self {
+                        bits as $T
+                    }
+                }
+            )*
+        };
+    }
+
+    #[cfg(not(armv6m))]
+    primitive!(u8 => u8, u16 => u16, u32 => u32, i32 => u32, usize => u32);
+
+    #[cfg(not(armv6m))]
+    impl Primitive for bool {
+        type Bits = u8;
+
+        #[inline(always)]
+        fn to_bits(self) -> u8 {
+            self as u8
+        }
+
+        #[inline(always)]
+        fn from_bits(bits: u8) -> Self {
+            bits != 0
+        }
+    }
+
+    #[cfg(not(armv6m))]
+    impl<T> Primitive for *mut T {
+        type Bits = u32;
+
+        #[inline(always)]
+        fn to_bits(self) -> u32 {
+            self as u32
+        }
+
+        #[inline(always)]
+        fn from_bits(bits: u32) -> Self {
+            bits as *mut T
+        }
+    }
+
+    /// The value of an emulated atomic.
+    ///
+    /// All operations are built on `load` and `update`, which use LDREX/STREX where the core has
+    /// the exclusive access instructions, and critical sections on ARMv6-M.
+    #[repr(transparent)]
+    struct Value<T> {
+        #[cfg(not(armv6m))]
+        inner: UnsafeCell<T>,
+        #[cfg(armv6m)]
+        inner: Mutex<Cell<T>>,
+    }
+
+    /// Safety: the value is only accessed with single-copy atomic loads and exclusive accesses.
+    #[cfg(not(armv6m))]
+    unsafe impl<T: Send> Sync for Value<T> {}
+
+    impl<T: Primitive> Value<T> {
+        #[inline]
+        const fn new(value: T) -> Self {
+            Value {
+                #[cfg(not(armv6m))]
+                inner: UnsafeCell::new(value),
+                #[cfg(armv6m)]
+                inner: Mutex::new(Cell::new(value)),
             }
-        })
-    }
-
-    ///fetch a value, apply the function and write back the modified value atomically
-    pub fn fetch_update<F>(&self, _: Ordering, _: Ordering, f: F) -> Result<u8, u8>
-    where
-        F: FnMut(u8) -> Option<u8>,
-    {
-        use crate::interrupt;
-        return interrupt::free(|| {
-            // Safety:
-            // 1. This is safe because we are on a single-core system, in an interrupt-free context.
-            // 2. No reference to the value can be acquired outside of this type.
-            let old = unsafe { *self.value.get() };
-            let mut f = f;
-            let new = f(old);
-            match new {
-                Some(new) => {
-                    unsafe { *self.value.get() = new };
-                    return Ok(old);
-                }
-                None => return Err(old),
+        }
+
+        #[inline]
+        fn get_mut(&mut self) -> &mut T {
+            match () {
+                #[cfg(not(armv6m))]
+                () => self.inner.get_mut(),
+                #[cfg(armv6m)]
+                () => self.inner.get_mut().get_mut(),
             }
-        });
+        }
+
+        #[inline]
+        fn into_inner(self) -> T {
+            match () {
+                #[cfg(not(armv6m))]
+                () => self.inner.into_inner(),
+                #[cfg(armv6m)]
+                () => self.inner.into_inner().into_inner(),
+            }
+        }
+
+        /// Loads the value.
+        #[inline]
+        fn load(&self) -> T {
+            match () {
+                #[cfg(not(armv6m))]
+                () => {
+                    compiler_fence(Ordering::SeqCst);
+                    // Safety: aligned loads of up to 32 bits are single-copy atomic.
+                    let value = unsafe { self.inner.get().read_volatile() };
+                    compiler_fence(Ordering::SeqCst);
+                    value
+                }
+                #[cfg(armv6m)]
+                () => interrupt::free(|cs| self.inner.borrow(cs).get()),
+            }
+        }
+
+        /// Replaces the value with the one returned by `f`, unless it returns `None`. `f` may be
+        /// called more than once. Returns the previous value, as `Ok` if it was replaced.
+        #[inline]
+        fn update(&self, mut f: impl FnMut(T) -> Option<T>) -> Result<T, T> {
+            match () {
+                #[cfg(not(armv6m))]
+                () => {
+                    compiler_fence(Ordering::SeqCst);
+                    // Safety: `Bits` has the size and alignment of `T`, and the value is only
+                    // written with exclusive accesses.
+                    let result = unsafe {
+                        asm::exclusive_update(self.inner.get() as *mut T::Bits, |bits| {
+                            f(T::from_bits(bits)).map(T::to_bits)
+                        })
+                    };
+                    compiler_fence(Ordering::SeqCst);
+                    result.map(T::from_bits).map_err(T::from_bits)
+                }
+                #[cfg(armv6m)]
+                () => interrupt::free(|cs| {
+                    let value = self.inner.borrow(cs);
+                    match f(value.get()) {
+                        Some(new) => Ok(value.replace(new)),
+                        None => Err(value.get()),
+                    }
+                }),
+            }
+        }
+
+        /// Replaces the value with `f(value)` and returns the previous value.
+        #[inline]
+        fn fetch(&self, f: impl Fn(T) -> T) -> T {
+            match self.update(|value| Some(f(value))) {
+                Ok(value) | Err(value) => value,
+            }
+        }
     }
-}
 
-#[cfg(not(all(not(feature = "atomic-cas"), cortex_m)))]
-pub use core::sync::atomic::AtomicBool;
+    /// Implements the operations common to all emulated atomic types.
+    macro_rules! atomic_common {
+        ($Atomic:ident, $T:ty) => {
+            atomic_common!([] $Atomic, $T);
+        };
+        ([$($P:ident)?] $Atomic:ty, $T:ty) => {
+            impl$(<$P>)? $Atomic {
+                /// Returns a mutable reference to the value.
+                #[inline]
+                pub fn get_mut(&mut self) -> &mut $T {
+                    self.value.get_mut()
+                }
 
-#[cfg(all(not(feature = "atomic-cas"), cortex_m))]
-/// An atomic `bool`.
-pub struct AtomicBool {
-    value: UnsafeCell<bool>,
-}
+                /// Consumes the atomic and returns the value.
+                #[inline]
+                pub fn into_inner(self) -> $T {
+                    self.value.into_inner()
+                }
 
-#[cfg(all(not(feature = "atomic-cas"), cortex_m))]
-impl AtomicBool {
-    /// Creates a new atomic bool.
-    pub const fn new(value: bool) -> Self {
-        Self {
-            value: UnsafeCell::new(value),
-        }
-    }
-
-    /// Loads the value.
-    pub fn load(&self, _: Ordering) -> bool {
-        use crate::interrupt;
-        interrupt::free(|| unsafe { *self.value.get() })
-    }
-
-    /// Stores a value.
-    pub fn store(&self, value: bool, _: Ordering) {
-        use crate::interrupt;
-        interrupt::free(|| unsafe {
-            *self.value.get() = value;
-        });
-    }
-
-    /// Compares the value and exchanges it.
-    pub fn compare_exchange(
-        &self,
-        current: bool,
-        new: bool,
-        _: Ordering,
-        _: Ordering,
-    ) -> Result<bool, bool> {
-        use crate::interrupt;
-        interrupt::free(|| {
-            // Safety:
-            // 1. This is safe because we are on a single-core system, in an interrupt-free context.
-            // 2. No reference to the value can be acquired outside of this type.
-            let value: bool = unsafe { *self.value.get() };
-
-            if value == current {
-                // Safety:
-                // 1. This is safe because we are on a single-core system, in an interrupt-free context.
-                // 2. No reference to the value can be acquired outside of this type.
-                // 3. The local `value` has been copied and not referenced.
-                unsafe {
-                    *self.value.get() = new;
-                }
-                Ok(value)
-            } else {
-                Err(value)
+                /// Loads the value.
+                #[inline]
+                pub fn load(&self, _: Ordering) -> $T {
+                    self.value.load()
+                }
+
+                /// Stores a value.
+                #[inline]
+                pub fn store(&self, new: $T, _: Ordering) {
+                    self.value.fetch(|_| new);
+                }
+
+                /// Stores a value and returns the previous value.
+                #[inline]
+                pub fn swap(&self, new: $T, _: Ordering) -> $T {
+                    self.value.fetch(|_| new)
+                }
+
+                /// Stores `new` if the value is `current`. Returns the previous value, as `Ok` if
+                /// it was `current`.
+                #[inline]
+                pub fn compare_exchange(
+                    &self,
+                    current: $T,
+                    new: $T,
+                    _: Ordering,
+                    _: Ordering,
+                ) -> Result<$T, $T> {
+                    self.value.update(|value| (value == current).then_some(new))
+                }
+
+                /// Stores `new` if the value is `current`. Returns the previous value, as `Ok` if
+                /// it was `current`. The emulation never fails spuriously.
+                #[inline]
+                pub fn compare_exchange_weak(
+                    &self,
+                    current: $T,
+                    new: $T,
+                    success: Ordering,
+                    failure: Ordering,
+                ) -> Result<$T, $T> {
+                    self.compare_exchange(current, new, success, failure)
+                }
+
+                /// Fetches the value, applies the function and writes back the modified value
+                /// atomically. Returns the previous value, as `Ok` if `f` returned `Some`.
+                #[inline]
+                pub fn fetch_update<F>(&self, _: Ordering, _: Ordering, f: F) -> Result<$T, $T>
+                where
+                    F: FnMut($T) -> Option<$T>,
+                {
+                    self.value.update(f)
+                }
+            }
+
+            impl$(<$P>)? fmt::Debug for $Atomic {
+                #[inline]
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+                }
             }
-        })
+        };
+    }
+
+    /// Defines an emulated atomic integer type.
+    macro_rules! atomic_int {
+        ($Atomic:ident, $T:ty, $name:literal) => {
+            #[doc = concat!("An atomic `", $name, "`.")]
+            #[repr(transparent)]
+            pub struct $Atomic {
+                value: Value<$T>,
+            }
+
+            impl $Atomic {
+                #[doc = concat!("Creates a new atomic ", $name, ".")]
+                #[inline]
+                pub const fn new(value: $T) -> Self {
+                    Self {
+                        value: Value::new(value),
+                    }
+                }
+
+                /// Adds to the value, wrapping around on overflow, and returns the previous value.
+                #[inline]
+                pub fn fetch_add(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| value.wrapping_add(val))
+                }
+
+                /// Subtracts from the value, wrapping around on overflow, and returns the previous
+                /// value.
+                #[inline]
+                pub fn fetch_sub(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| value.wrapping_sub(val))
+                }
+
+                /// Bitwise "and" with the value, returns the previous value.
+                #[inline]
+                pub fn fetch_and(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| value & val)
+                }
+
+                /// Bitwise "nand" with the value, returns the previous value.
+                #[inline]
+                pub fn fetch_nand(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| !(value & val))
+                }
+
+                /// Bitwise "or" with the value, returns the previous value.
+                #[inline]
+                pub fn fetch_or(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| value | val)
+                }
+
+                /// Bitwise "xor" with the value, returns the previous value.
+                #[inline]
+                pub fn fetch_xor(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| value ^ val)
+                }
+
+                /// Stores the maximum of the value and `val`, returns the previous value.
+                #[inline]
+                pub fn fetch_max(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| value.max(val))
+                }
+
+                /// Stores the minimum of the value and `val`, returns the previous value.
+                #[inline]
+                pub fn fetch_min(&self, val: $T, _: Ordering) -> $T {
+                    self.value.fetch(|value| value.min(val))
+                }
+            }
+
+            atomic_common!($Atomic, $T);
+
+            impl Default for $Atomic {
+                #[inline]
+                fn default() -> Self {
+                    Self::new(0)
+                }
+            }
+
+            impl From<$T> for $Atomic {
+                #[inline]
+                fn from(value: $T) -> Self {
+                    Self::new(value)
+                }
+            }
+        };
+    }
+
+    // ----------------------------Integers----------------------------
+
+    atomic_int!(AtomicU8, u8, "u8");
+    atomic_int!(AtomicU16, u16, "u16");
+    atomic_int!(AtomicU32, u32, "u32");
+    atomic_int!(AtomicI32, i32, "i32");
+    atomic_int!(AtomicUsize, usize, "usize");
+
+    // ----------------------------AtomicBool----------------------------
+
+    /// An atomic `bool`.
+    #[repr(transparent)]
+    pub struct AtomicBool {
+        value: Value<bool>,
+    }
+
+    impl AtomicBool {
+        /// Creates a new atomic bool.
+        #[inline]
+        pub const fn new(value: bool) -> Self {
+            Self {
+                value: Value::new(value),
+            }
+        }
+
+        /// Logical "and" with the value, returns the previous value.
+        #[inline]
+        pub fn fetch_and(&self, val: bool, _: Ordering) -> bool {
+            self.value.fetch(|value| value & val)
+        }
+
+        /// Logical "nand" with the value, returns the previous value.
+        #[inline]
+        pub fn fetch_nand(&self, val: bool, _: Ordering) -> bool {
+            self.value.fetch(|value| !(value & val))
+        }
+
+        /// Logical "or" with the value, returns the previous value.
+        #[inline]
+        pub fn fetch_or(&self, val: bool, _: Ordering) -> bool {
+            self.value.fetch(|value| value | val)
+        }
+
+        /// Logical "xor" with the value, returns the previous value.
+        #[inline]
+        pub fn fetch_xor(&self, val: bool, _: Ordering) -> bool {
+            self.value.fetch(|value| value ^ val)
+        }
+    }
+
+    atomic_common!(AtomicBool, bool);
+
+    impl Default for AtomicBool {
+        #[inline]
+        fn default() -> Self {
+            Self::new(false)
+        }
+    }
+
+    impl From<bool> for AtomicBool {
+        #[inline]
+        fn from(value: bool) -> Self {
+            Self::new(value)
+        }
+    }
+
+    // ----------------------------AtomicPtr----------------------------
+
+    /// An atomic raw pointer.
+    #[repr(transparent)]
+    pub struct AtomicPtr<T> {
+        value: Value<*mut T>,
+    }
+
+    /// Safety: the pointer is only accessed atomically. Like `core`'s `AtomicPtr`, it can be
+    /// shared and sent regardless of `T`.
+    unsafe impl<T> Sync for AtomicPtr<T> {}
+    unsafe impl<T> Send for AtomicPtr<T> {}
+
+    impl<T> AtomicPtr<T> {
+        /// Creates a new atomic pointer.
+        #[inline]
+        pub const fn new(value: *mut T) -> Self {
+            Self {
+                value: Value::new(value),
+            }
+        }
+    }
+
+    atomic_common!([T] AtomicPtr<T>, *mut T);
+
+    impl<T> Default for AtomicPtr<T> {
+        #[inline]
+        fn default() -> Self {
+            Self::new(core::ptr::null_mut())
+        }
+    }
+
+    impl<T> From<*mut T> for AtomicPtr<T> {
+        #[inline]
+        fn from(value: *mut T) -> Self {
+            Self::new(value)
+        }
     }
 }
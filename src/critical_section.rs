@@ -0,0 +1,104 @@
+//! `critical-section` implementations
+//!
+//! The restore state is `true` if interrupts were enabled when the critical section was acquired.
+
+#[cfg(all(
+    feature = "critical-section-single-core",
+    feature = "critical-section-multi-core"
+))]
+compile_error!(
+    "The `critical-section-single-core` and `critical-section-multi-core` features are mutually exclusive."
+);
+
+use critical_section::{set_impl, Impl, RawRestoreState};
+
+#[cfg(feature = "critical-section-multi-core")]
+use crate::atomic::{AtomicU32, Ordering};
+use crate::interrupt;
+#[cfg(feature = "critical-section-single-core")]
+use crate::register::primask;
+
+/// Critical sections that disable interrupts in the current core.
+#[cfg(feature = "critical-section-single-core")]
+struct SingleCoreCriticalSection;
+
+#[cfg(feature = "critical-section-single-core")]
+set_impl!(SingleCoreCriticalSection);
+
+#[cfg(feature = "critical-section-single-core")]
+unsafe impl Impl for SingleCoreCriticalSection {
+    unsafe fn acquire() -> RawRestoreState {
+        let was_active = primask::read().is_active();
+        interrupt::disable();
+        was_active
+    }
+
+    unsafe fn release(was_active: RawRestoreState) {
+        // Only re-enable interrupts if they were enabled before the critical section.
+        if was_active {
+            interrupt::enable()
+        }
+    }
+}
+
+/// Critical sections that disable interrupts in the current core, and exclude the other cores
+/// with a spin lock.
+#[cfg(feature = "critical-section-multi-core")]
+struct MultiCoreCriticalSection;
+
+/// The lock shared by the critical sections of all cores.
+#[cfg(feature = "critical-section-multi-core")]
+static LOCK: crate::sync::SpinLock = crate::sync::SpinLock::new();
+
+/// The ID of the core holding [`LOCK`], or [`NO_CORE`].
+#[cfg(feature = "critical-section-multi-core")]
+static OWNER: AtomicU32 = AtomicU32::new(NO_CORE);
+
+/// The number of nested critical sections of the core holding [`LOCK`].
+#[cfg(feature = "critical-section-multi-core")]
+static DEPTH: AtomicU32 = AtomicU32::new(0);
+
+/// The [`OWNER`] while no core holds the lock.
+#[cfg(feature = "critical-section-multi-core")]
+const NO_CORE: u32 = u32::MAX;
+
+#[cfg(feature = "critical-section-multi-core")]
+extern "Rust" {
+    /// Returns the ID of the current core, see the `critical-section-multi-core` feature.
+    fn _cortex_m_core_id() -> u32;
+}
+
+#[cfg(feature = "critical-section-multi-core")]
+set_impl!(MultiCoreCriticalSection);
+
+#[cfg(feature = "critical-section-multi-core")]
+unsafe impl Impl for MultiCoreCriticalSection {
+    unsafe fn acquire() -> RawRestoreState {
+        let primask = interrupt::save();
+        let core = _cortex_m_core_id();
+
+        // Only a core holding the lock stores its ID, so the current core can't race with itself
+        // once its interrupts are disabled.
+        if OWNER.load(Ordering::Relaxed) == core {
+            DEPTH.fetch_add(1, Ordering::Relaxed);
+        } else {
+            LOCK.lock();
+            OWNER.store(core, Ordering::Relaxed);
+            DEPTH.store(1, Ordering::Relaxed);
+        }
+        primask.is_active()
+    }
+
+    unsafe fn release(was_active: RawRestoreState) {
+        // The outermost critical section of the core releases the lock.
+        if DEPTH.fetch_sub(1, Ordering::Relaxed) == 1 {
+            OWNER.store(NO_CORE, Ordering::Relaxed);
+            LOCK.unlock();
+        }
+
+        // Only re-enable interrupts if they were enabled before the critical section.
+        if was_active {
+            interrupt::enable()
+        }
+    }
+}
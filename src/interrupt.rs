@@ -2,9 +2,92 @@
 
 #[cfg(cortex_m)]
 use core::arch::asm;
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
 #[cfg(cortex_m)]
 use core::sync::atomic::{compiler_fence, Ordering};
 
+#[cfg(cortex_m)]
+use crate::register::primask;
+use crate::register::primask::Primask;
+#[cfg(all(cortex_m, not(armv6m), not(armv8m_base)))]
+use crate::register::{basepri, faultmask, faultmask::Faultmask};
+
+/// Critical section token.
+///
+/// Proves that the code holding it runs with interrupts disabled in the current core, see
+/// [`free`]. Critical sections can be nested, the token of an outer critical section stays
+/// valid in the inner ones.
+#[derive(Debug)]
+#[allow(clippy::missing_inline_in_public_items)]
+pub struct CriticalSection {
+    // Neither `Send` nor `Sync`, the token is only valid in the context that created it.
+    _marker: PhantomData<*mut ()>,
+}
+
+impl CriticalSection {
+    /// Creates a critical section token.
+    ///
+    /// # Safety
+    ///
+    /// Interrupts must be disabled in the current core for as long as the token, and any
+    /// reference to it, lives.
+    #[inline]
+    pub unsafe fn new() -> Self {
+        CriticalSection {
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// A mutex based on critical sections.
+///
+/// The value can only be borrowed with a [`CriticalSection`] token, so it is never accessed
+/// concurrently by an interrupt handler. Mutable access requires interior mutability, e.g.
+/// `Mutex<Cell<T>>` or `Mutex<RefCell<T>>`.
+///
+/// Critical sections don't exclude the other cores, so the mutex can't be shared between threads
+/// with the `multi-core` feature.
+pub struct Mutex<T> {
+    inner: UnsafeCell<T>,
+}
+
+impl<T> Mutex<T> {
+    /// Creates a new mutex.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Mutex {
+            inner: UnsafeCell::new(value),
+        }
+    }
+
+    /// Borrows the value for the lifetime of the critical section.
+    #[inline]
+    pub fn borrow<'cs>(&'cs self, _cs: &'cs CriticalSection) -> &'cs T {
+        // Safety: the value is only shared with the contexts holding a critical section token,
+        // which are not interrupted.
+        unsafe { &*self.inner.get() }
+    }
+
+    /// Returns a mutable reference to the value. No critical section is needed, as the mutex
+    /// is borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    /// Consumes the mutex and returns the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+/// Safety: on single-core systems, the value is only accessed in critical sections, which
+/// can't run concurrently.
+#[cfg(not(feature = "multi-core"))]
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
 /// Trait for enums of external interrupt numbers.
 ///
 /// This trait should be implemented by a peripheral access crate (PAC)
@@ -52,8 +135,44 @@ pub unsafe fn enable() {
     asm!("cpsie i", options(nomem, nostack, preserves_flags));
 }
 
+/// Disables all interrupts in the current core and returns the previous PRIMASK, to be passed to
+/// [`restore`].
+#[inline]
+pub(crate) fn save() -> Primask {
+    match () {
+        #[cfg(cortex_m)]
+        () => {
+            let primask = primask::read();
+            disable();
+            primask
+        }
+        #[cfg(not(cortex_m))]
+        () => panic!("interrupts can only be disabled on cortex-m platforms"),
+    }
+}
+
+/// Enables the interrupts again if they were enabled when [`save`] returned `primask`.
+///
+/// # Safety
+///
+/// - `primask` must have been returned by [`save`] in the current core, and the sections saved
+///   since must have been restored.
+#[inline]
+pub(crate) unsafe fn restore(primask: Primask) {
+    #[cfg(cortex_m)]
+    if primask.is_active() {
+        enable();
+    }
+
+    #[cfg(not(cortex_m))]
+    let _ = primask;
+}
+
 /// Execute closure `f` with interrupts disabled in the current core.
 ///
+/// `f` receives a [`CriticalSection`] token, which proves that interrupts are disabled, e.g. to
+/// borrow the value of a [`Mutex`].
+///
 /// This method does not synchronise multiple cores and may disable required
 /// interrupts on some platforms; see the `critical-section` crate for a cross-platform
 /// way to enter a critical section which provides a `CriticalSection` token.
@@ -64,20 +183,15 @@ pub unsafe fn enable() {
 #[inline]
 pub fn free<F, R>(f: F) -> R
 where
-    F: FnOnce() -> R,
+    F: FnOnce(&CriticalSection) -> R,
 {
-    let primask = crate::register::primask::read();
+    let primask = save();
 
-    // disable interrupts
-    disable();
+    let r = f(unsafe { &CriticalSection::new() });
 
-    let r = f();
-
-    // If the interrupts were active before our `disable` call, then re-enable
-    // them. Otherwise, keep them disabled
-    if primask.is_active() {
-        unsafe { enable() }
-    }
+    // If the interrupts were active before `save`, then re-enable them. Otherwise, keep them
+    // disabled.
+    unsafe { restore(primask) }
 
     r
 }
@@ -89,7 +203,213 @@ where
 #[inline]
 pub fn free<F, R>(_: F) -> R
 where
-    F: FnOnce() -> R,
+    F: FnOnce(&CriticalSection) -> R,
 {
     panic!("cortex_m::interrupt::free() is only functional on cortex-m platforms");
 }
+
+/// Execute closure `f` with the interrupts of priority `prio` or lower masked in the current core.
+///
+/// `prio` is a raw priority as written by `NVIC::set_priority`, a higher value is a lower
+/// priority: the interrupts with a priority value of `prio` or more are masked. Interrupts of
+/// higher priority stay enabled and may preempt `f`. Devices only implement the most significant
+/// bits of the priority, `prio` must be non-zero in these bits: the others read as zero and are
+/// ignored by the processor.
+///
+/// BASEPRI is only ever raised: when nested in a section that already masks more interrupts, the
+/// mask is left unchanged. The previous mask is restored when `f` returns.
+///
+/// `f` doesn't receive a [`CriticalSection`] token, as it can be preempted.
+///
+/// # Panics
+///
+/// Panics if `prio` is 0, which can't be written to BASEPRI; use [`free`] instead. Also panics if
+/// `prio` is 0 in the implemented priority bits, unless an enclosing section already raised
+/// BASEPRI, which can't be told apart from a write that was ignored.
+///
+/// # Availability
+///
+/// BASEPRI doesn't exist on ARMv6-M and ARMv8-M Baseline, where only [`free`] is available.
+#[cfg(all(cortex_m, not(armv6m), not(armv8m_base)))]
+#[inline]
+pub fn free_below<F, R>(prio: u8, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    use crate::register::basepri_max;
+
+    assert!(prio != 0, "a priority of 0 can't be masked with BASEPRI");
+
+    let previous = basepri::read();
+    basepri_max::write(prio);
+    // A write of 0 in the implemented bits is ignored and would leave the interrupts unmasked.
+    assert!(
+        basepri::read() != 0,
+        "the priority has no implemented bit set and can't be masked with BASEPRI"
+    );
+    // Ensure no subsequent memory accesses are reordered to before the interrupts are masked.
+    compiler_fence(Ordering::SeqCst);
+
+    let r = f();
+
+    // Ensure no preceeding memory accesses are reordered to after the interrupts are unmasked.
+    compiler_fence(Ordering::SeqCst);
+    // Safety: `previous` is the mask of the enclosing context, nested sections are left in
+    // reverse order.
+    unsafe { basepri::write(previous) }
+
+    r
+}
+
+// Make a `free_below()` function available to allow checking dependencies without specifying a
+// target, but that will panic at runtime if executed.
+#[doc(hidden)]
+#[cfg(not(cortex_m))]
+#[inline]
+pub fn free_below<F, R>(_: u8, _: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    panic!("cortex_m::interrupt::free_below() is only functional on cortex-m platforms");
+}
+
+/// A snapshot of the interrupt masks of the current core: PRIMASK, and FAULTMASK and BASEPRI
+/// where they exist.
+///
+/// Returned by [`acquire`] and [`acquire_faultmask`], to be passed back to [`release`]. This is
+/// the shape of the critical section primitives expected by RTOS ports.
+#[cfg(cortex_m)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[allow(clippy::missing_inline_in_public_items)]
+pub struct State {
+    primask: Primask,
+    #[cfg(all(not(armv6m), not(armv8m_base)))]
+    faultmask: Faultmask,
+    #[cfg(all(not(armv6m), not(armv8m_base)))]
+    basepri: u8,
+}
+
+#[cfg(cortex_m)]
+impl State {
+    /// Reads the interrupt masks of the current core.
+    #[inline]
+    pub fn read() -> Self {
+        State {
+            primask: primask::read(),
+            #[cfg(all(not(armv6m), not(armv8m_base)))]
+            faultmask: faultmask::read(),
+            #[cfg(all(not(armv6m), not(armv8m_base)))]
+            basepri: basepri::read(),
+        }
+    }
+}
+
+// Make a `State` type available to allow checking dependencies without specifying a target, but
+// that will panic at runtime if read.
+#[doc(hidden)]
+#[cfg(not(cortex_m))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[allow(clippy::missing_inline_in_public_items)]
+pub struct State {
+    _private: (),
+}
+
+#[cfg(not(cortex_m))]
+impl State {
+    #[doc(hidden)]
+    #[inline]
+    pub fn read() -> Self {
+        panic!("cortex_m::interrupt::State::read() is only functional on cortex-m platforms");
+    }
+}
+
+/// Disables all interrupts in the current core and returns the previous [`State`].
+///
+/// The interrupts are enabled again by passing the state to [`release`], sections can be nested
+/// as long as they are released in reverse order.
+#[cfg(cortex_m)]
+#[inline]
+pub fn acquire() -> State {
+    let state = State::read();
+    disable();
+    state
+}
+
+// Make an `acquire()` function available to allow checking dependencies without specifying a
+// target, but that will panic at runtime if executed.
+#[doc(hidden)]
+#[cfg(not(cortex_m))]
+#[inline]
+pub fn acquire() -> State {
+    panic!("cortex_m::interrupt::acquire() is only functional on cortex-m platforms");
+}
+
+/// Masks all exceptions except NMI in the current core, by setting FAULTMASK, and returns the
+/// previous [`State`].
+///
+/// This raises the execution priority to that of HardFault, so that not even faults escalate
+/// while e.g. a flash programming sequence runs. FAULTMASK can't be set in the NMI and HardFault
+/// handlers, and is cleared by the processor on the return from any exception but NMI.
+///
+/// The exceptions are unmasked again by passing the state to [`release`].
+#[cfg(all(cortex_m, not(armv6m), not(armv8m_base)))]
+#[inline]
+pub fn acquire_faultmask() -> State {
+    let state = State::read();
+    unsafe {
+        asm!("cpsid f", options(nomem, nostack, preserves_flags));
+    }
+
+    // Ensure no subsequent memory accesses are reordered to before the exceptions are masked.
+    compiler_fence(Ordering::SeqCst);
+    state
+}
+
+// Make an `acquire_faultmask()` function available to allow checking dependencies without
+// specifying a target, but that will panic at runtime if executed.
+#[doc(hidden)]
+#[cfg(not(cortex_m))]
+#[inline]
+pub fn acquire_faultmask() -> State {
+    panic!("cortex_m::interrupt::acquire_faultmask() is only functional on cortex-m platforms");
+}
+
+/// Restores the interrupt masks of the current core to `state`.
+///
+/// # Safety
+///
+/// - `state` must have been returned by [`acquire`] or [`acquire_faultmask`] in the current
+///   core, and the sections acquired since must have been released.
+#[cfg(cortex_m)]
+#[inline]
+pub unsafe fn release(state: State) {
+    // Ensure no preceeding memory accesses are reordered to after the masks are restored.
+    compiler_fence(Ordering::SeqCst);
+
+    // Restore BASEPRI and FAULTMASK before PRIMASK, which is set by `acquire`, so that no
+    // interrupt is taken before all the masks are restored.
+    #[cfg(all(not(armv6m), not(armv8m_base)))]
+    {
+        basepri::write(state.basepri);
+        if state.faultmask.is_active() {
+            asm!("cpsie f", options(nomem, nostack, preserves_flags));
+        } else {
+            asm!("cpsid f", options(nomem, nostack, preserves_flags));
+        }
+    }
+
+    if state.primask.is_active() {
+        enable();
+    } else {
+        disable();
+    }
+}
+
+// Make a `release()` function available to allow checking dependencies without specifying a
+// target, but that will panic at runtime if executed.
+#[doc(hidden)]
+#[cfg(not(cortex_m))]
+#[inline]
+pub unsafe fn release(_: State) {
+    panic!("cortex_m::interrupt::release() is only functional on cortex-m platforms");
+}
@@ -19,12 +19,47 @@
 //! or critical sections are managed as part of an RTOS. In these cases, you should use
 //! a target-specific implementation instead, typically provided by a HAL or RTOS crate.
 //!
+//! ## `critical-section-multi-core`
+//!
+//! This feature enables a `critical-section` implementation for multi-core targets. Like the
+//! single-core implementation it disables interrupts, and in addition it takes a spin lock shared
+//! by all cores. It implies the `multi-core` feature and can't be combined with
+//! `critical-section-single-core`.
+//!
+//! The lock is reentrant: a critical section nested in one of the same core doesn't take it again.
+//! To tell the cores apart, the HAL must provide their IDs with a function that is unique to each
+//! core and never returns `u32::MAX`:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! fn _cortex_m_core_id() -> u32 {
+//!     // e.g. read the CPUID register of the SIO block on the RP2040.
+//!     unsafe { core::ptr::read_volatile(0xd000_0000 as *const u32) }
+//! }
+//! ```
+//!
+//! As with the single-core implementation, it is **unsound** for code running in unprivileged
+//! mode.
+//!
 //! ## `cm7-r0p1`
 //!
 //! This feature enables workarounds for errata found on Cortex-M7 chips with revision r0p1. Some
 //! functions in this crate only work correctly on those chips if this Cargo feature is enabled
 //! (the functions are documented accordingly).
 //!
+//! ## `sched-pendsv`
+//!
+//! This feature provides the `PendSV` exception handler, which switches between the thread contexts
+//! of the `sched` module. The scheduler that selects the next thread is registered with
+//! `sched::pendsv::set_scheduler`. The handler can't be combined with a `PendSV` handler defined
+//! elsewhere, e.g. with `cortex-m-rt`'s `#[exception]` attribute.
+//!
+//! ## `syscall-svc`
+//!
+//! This feature provides the `SVCall` exception handler, which dispatches system calls made with
+//! the `syscall!` macro to the handlers registered with `syscall::set_handlers`. Like `sched-pendsv`,
+//! it can't be combined with an `SVCall` handler defined elsewhere.
+//!
 //! # Minimum Supported Rust Version (MSRV)
 //!
 //! This crate is guaranteed to compile on stable Rust 1.60 and up. It *might*
@@ -56,6 +91,15 @@
 #[macro_use]
 mod macros;
 
+#[cfg(all(
+    cortex_m,
+    any(
+        feature = "critical-section-single-core",
+        feature = "critical-section-multi-core"
+    )
+))]
+mod critical_section;
+
 pub mod asm;
 pub mod atomic;
 #[cfg(armv8m)]
@@ -71,5 +115,13 @@ pub mod sched;
 #[allow(missing_docs)]
 pub mod semih;
 pub mod sync;
+pub mod syscall;
 pub mod types;
 pub mod volatile;
+
+/// Used to reexport items for use in macros. Do not use directly.
+/// Not covered by semver guarantees.
+#[doc(hidden)]
+pub mod _export {
+    pub use critical_section;
+}
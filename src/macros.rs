@@ -61,39 +61,67 @@ macro_rules! hprintln {
 }
 
 /// Macro for doing a system call.
-
+///
+/// Issues `svc $num` with up to four arguments in r0-r3 and evaluates to the tuple `(r0, r1)`
+/// of the results set by the kernel, see [`syscall`](mod@crate::syscall). The system call number must
+/// be a constant and the arguments must be convertible to `u32` with `as`.
+///
+/// # Example
+///
+/// ``` no_run
+/// use cortex_m::syscall;
+///
+/// const SYS_WRITE: u8 = 4;
+///
+/// fn write(fd: u32, buf: &[u8]) -> u32 {
+///     let (written, _) = syscall!(SYS_WRITE, fd, buf.as_ptr(), buf.len());
+///     written
+/// }
+/// ```
 #[macro_export]
 macro_rules! syscall {
     ($num:expr) => {
-        unsafe {
-            #[cfg(cortex_m)]
-            asm!("svc {0}", $num);
-        }
+        $crate::syscall!(@svc $num, 0, 0, 0, 0)
     };
     ($num:expr, $arg0:expr) => {
-        unsafe {
-            #[cfg(cortex_m)]
-            asm!("mov r0, {0}", "svc {1}", in(reg)$arg0, const $num);
-        }
+        $crate::syscall!(@svc $num, $arg0, 0, 0, 0)
     };
-    ($num:expr, $arg0:expr, arg1:expr) => {
-        unsafe {
-            #[cfg(cortex_m)]
-            asm!("mov r0, {0}", "mov r1, {1}", "svc {2}", in(reg)$arg0, in(reg)$arg1, const $num);
-        }
+    ($num:expr, $arg0:expr, $arg1:expr) => {
+        $crate::syscall!(@svc $num, $arg0, $arg1, 0, 0)
     };
-    ($num:expr, $arg0:expr, arg1:expr, arg2:expr) => {
-        unsafe {
-            #[cfg(cortex_m)]
-            asm!("mov r0, {0}", "mov r1, {1}", "mov r2, {2}", "svc {3}", in(reg)$arg0, in(reg)$arg1, in(reg)$arg2, const $num);
-        }
+    ($num:expr, $arg0:expr, $arg1:expr, $arg2:expr) => {
+        $crate::syscall!(@svc $num, $arg0, $arg1, $arg2, 0)
     };
-    ($num:expr, $arg0:expr, arg1:expr, arg2:expr, arg3:expr) => {
-        unsafe {
-            #[cfg(cortex_m)]
-            asm!("mov r0, {0}", "mov r1, {1}", "mov r2, {2}", "mov r3, {3}", "svc {4}", in(reg)$arg0, in(reg)$arg1, in(reg)$arg2, in(reg)$arg3, const $num);
-        }
+    ($num:expr, $arg0:expr, $arg1:expr, $arg2:expr, $arg3:expr) => {
+        $crate::syscall!(@svc $num, $arg0, $arg1, $arg2, $arg3)
     };
+    (@svc $num:expr, $arg0:expr, $arg1:expr, $arg2:expr, $arg3:expr) => {{
+        let ret: (u32, u32) = match () {
+            #[cfg(target_arch = "arm")]
+            () => {
+                let r0: u32;
+                let r1: u32;
+                unsafe {
+                    ::core::arch::asm!(
+                        "svc {num}",
+                        num = const $num,
+                        inout("r0") ($arg0) as u32 => r0,
+                        inout("r1") ($arg1) as u32 => r1,
+                        inout("r2") ($arg2) as u32 => _,
+                        inout("r3") ($arg3) as u32 => _,
+                        options(nostack),
+                    );
+                }
+                (r0, r1)
+            }
+            #[cfg(not(target_arch = "arm"))]
+            () => {
+                let _ = ($num, $arg0, $arg1, $arg2, $arg3);
+                panic!("cortex_m::syscall!() is only functional on cortex-m platforms")
+            }
+        };
+        ret
+    }};
 }
 
 /// Macro to create a mutable reference to a statically allocated value
@@ -200,3 +228,130 @@ const CPASS: () = ();
 /// ```
 #[allow(dead_code)]
 const CPASS_ATTR: () = ();
+
+/// Macro to declare an enum of external interrupt numbers.
+///
+/// Generates the enum, with `#[repr(u16)]` and derives of `Clone` and `Copy`, and implements
+/// [`InterruptNumber`](crate::interrupt::InterruptNumber) for it. The requirements of the trait are
+/// checked at compile time: the variants are converted to their discriminants, which can't be
+/// duplicated, and each number must fit the NVIC, i.e. be lower than 496.
+///
+/// **The macro always derives `Clone` and `Copy`, don't derive them again on the enum**: a
+/// `#[derive(Clone)]` or `#[derive(Copy)]` of your own conflicts with the generated ones and fails
+/// to compile with E0119. Other derives, like `Debug` or `PartialEq`, are forwarded as usual.
+///
+/// The enum also gets:
+///
+/// - `TryFrom<u16>`, which returns the invalid number as the error.
+/// - `VARIANTS`, a slice of all the variants in declaration order, and `iter()`, an iterator over
+///   them, e.g. to set up the priorities of all interrupts.
+///
+/// # Example
+///
+/// ```
+/// use cortex_m::interrupt::InterruptNumber;
+/// use cortex_m::interrupt_enum;
+///
+/// interrupt_enum! {
+///     /// The interrupts of the device.
+///     #[derive(Debug, PartialEq, Eq)]
+///     pub enum Interrupt {
+///         /// UART0 global interrupt.
+///         UART0 = 0,
+///         TIMER0 = 1,
+///         GPIO = 5,
+///     }
+/// }
+///
+/// assert_eq!(Interrupt::GPIO.number(), 5);
+/// assert_eq!(Interrupt::try_from(1), Ok(Interrupt::TIMER0));
+/// assert_eq!(Interrupt::try_from(2), Err(2));
+/// assert_eq!(Interrupt::iter().count(), 3);
+/// ```
+///
+/// Duplicated numbers fail to compile:
+///
+/// ``` compile_fail
+/// cortex_m::interrupt_enum! {
+///     enum Interrupt {
+///         UART0 = 0,
+///         TIMER0 = 0,
+///     }
+/// }
+/// ```
+///
+/// So do derives of `Clone` or `Copy`:
+///
+/// ``` compile_fail,E0119
+/// cortex_m::interrupt_enum! {
+///     #[derive(Clone, Copy)]
+///     enum Interrupt {
+///         UART0 = 0,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! interrupt_enum {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident = $number:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy)]
+        #[repr(u16)]
+        $vis enum $name {
+            $(
+                $(#[$variant_attr])*
+                $variant = $number,
+            )*
+        }
+
+        const _: () = {
+            $(
+                assert!(
+                    ($name::$variant as u16) < 496,
+                    concat!("the number of `", stringify!($variant), "` is out of the range of the NVIC")
+                );
+            )*
+        };
+
+        // Safety: the variants convert to their discriminants, which are distinct constants.
+        unsafe impl $crate::interrupt::InterruptNumber for $name {
+            #[inline]
+            fn number(self) -> u16 {
+                self as u16
+            }
+        }
+
+        impl ::core::convert::TryFrom<u16> for $name {
+            type Error = u16;
+
+            #[inline]
+            fn try_from(number: u16) -> ::core::result::Result<Self, u16> {
+                $(
+                    if number == $name::$variant as u16 {
+                        return ::core::result::Result::Ok($name::$variant);
+                    }
+                )*
+                ::core::result::Result::Err(number)
+            }
+        }
+
+        #[allow(dead_code)]
+        impl $name {
+            /// All the variants, in declaration order.
+            pub const VARIANTS: &'static [$name] = &[$($name::$variant),*];
+
+            /// Returns an iterator over all the variants, in declaration order.
+            #[inline]
+            pub fn iter() -> ::core::iter::Copied<::core::slice::Iter<'static, $name>> {
+                $name::VARIANTS.iter().copied()
+            }
+        }
+    };
+}
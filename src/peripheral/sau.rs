@@ -8,7 +8,7 @@
 //! For reference please check the section B8.3 of the Armv8-M Architecture Reference Manual.
 
 use crate::peripheral::SAU;
-use crate::vtypes::{RO, RW};
+use crate::volatile::{RO, RW};
 use bitfield::bitfield;
 
 /// Register block
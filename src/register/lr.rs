@@ -19,13 +19,16 @@ pub unsafe fn write(bits: u32) {
     asm!("mov lr, {}", in(reg) bits, options(nomem, nostack, preserves_flags));
 }
 
+/// Returns whether the exception being handled was taken from a context using MSP.
 ///
+/// Reads the EXC_RETURN value from lr, so this must be used at the beginning of an exception
+/// handler, before lr is overwritten.
 #[macro_export]
 macro_rules! irq_is_msp {
     () => {
         {
             let lr: u32;
-            unsafe { asm!("mov {}, lr", out(reg) lr, options(nostack, preserves_flags)) };
+            unsafe { ::core::arch::asm!("mov {}, lr", out(reg) lr, options(nostack, preserves_flags)) };
             lr & 0x4 == 0
         }
     };
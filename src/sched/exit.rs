@@ -0,0 +1,85 @@
+//! Thread exit trampoline
+//!
+//! A thread created by [`ThreadContext::from_empty`](super::ThreadContext::from_empty) returns into
+//! the exit trampoline. The trampoline records the value returned by the entry point in the
+//! [`ThreadExit`] record stored at the top of the thread's stack and calls the finalizer of the
+//! thread with it.
+//!
+//! The trampoline finds the record through the stack pointer: when the entry point returns, the
+//! stack pointer is back at the top of the initial exception frame, where the record is stored.
+
+#[cfg(cortex_m)]
+use core::arch::global_asm;
+
+/// Struct: ThreadExit
+///
+/// Describes a thread that returned from its entry point.
+/// The record is stored on the stack of the thread, above its initial context.
+#[repr(C)]
+#[derive(Debug)]
+#[allow(clippy::missing_inline_in_public_items)]
+pub struct ThreadExit {
+    /// The identifier of the thread, see [`ThreadAttrs::id`](super::ThreadAttrs::id).
+    pub id: usize,
+
+    /// The number of arguments passed to the thread.
+    pub argc: usize,
+
+    /// The arguments passed to the thread.
+    /// The thread no longer uses them, so their memory can be reclaimed.
+    pub argv: *const *const u8,
+
+    /// The value returned by the entry point of the thread.
+    pub ret: usize,
+
+    /// The finalizer to call with the record.
+    pub(super) finalizer: extern "C" fn(exit: &ThreadExit) -> !,
+}
+
+/// Returns the address the entry point of a thread returns to.
+#[inline]
+pub(super) fn trampoline() -> usize {
+    #[cfg(cortex_m)]
+    {
+        extern "C" {
+            fn __cortex_m_sched_exit();
+        }
+        __cortex_m_sched_exit as unsafe extern "C" fn() as usize
+    }
+
+    #[cfg(not(cortex_m))]
+    {
+        // Threads can't be run natively, the address is only used to identify the trampoline.
+        extern "C" fn __cortex_m_sched_exit() {
+            panic!("cortex_m::sched threads are only functional on cortex-m platforms");
+        }
+        __cortex_m_sched_exit as extern "C" fn() as usize
+    }
+}
+
+/// Called by the exit trampoline, with the value returned by the entry point of the thread and
+/// the exit record at the top of its stack.
+#[cfg(cortex_m)]
+unsafe extern "C" fn exit(ret: usize, record: *mut ThreadExit) -> ! {
+    (*record).ret = ret;
+    ((*record).finalizer)(&*record)
+}
+
+// The stack pointer points to the exit record of the thread, which is passed with the value
+// returned by the entry point (r0) to `exit`. `exit` is out of the branch range on ARMv6-M, so
+// its address is loaded instead.
+#[cfg(cortex_m)]
+global_asm!(
+    ".section .text.__cortex_m_sched_exit, \"ax\", %progbits",
+    ".global __cortex_m_sched_exit",
+    ".hidden __cortex_m_sched_exit",
+    ".type __cortex_m_sched_exit, %function",
+    ".thumb_func",
+    "__cortex_m_sched_exit:",
+    "    mov r1, sp",
+    "    ldr r2, ={exit}",
+    "    bx r2",
+    ".ltorg",
+    ".size __cortex_m_sched_exit, . - __cortex_m_sched_exit",
+    exit = sym exit,
+);
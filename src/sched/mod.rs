@@ -0,0 +1,754 @@
+//! Module: sched
+
+use crate::peripheral;
+#[cfg(cortex_m)]
+use crate::register;
+use crate::register::control::{Control, Npriv, Spsel};
+use core::fmt;
+use core::marker::PhantomData;
+
+mod exit;
+#[cfg(feature = "sched-pendsv")]
+pub mod pendsv;
+#[cfg(native)]
+pub mod sim;
+
+#[cfg(test)]
+mod test;
+
+pub use exit::ThreadExit;
+
+/// Type: CtxPtr
+pub type CtxPtr = *const u32;
+
+/// EXC_RETURN value to return to Thread mode, using PSP and a basic frame.
+pub const EXC_RETURN_THREAD_PSP: u32 = 0xFFFF_FFFD;
+
+/// EXC_RETURN value to return to Thread mode, using PSP and an extended (FPU) frame.
+pub const EXC_RETURN_THREAD_PSP_FPU: u32 = 0xFFFF_FFED;
+
+/// EXC_RETURN bit that is cleared if the exception frame is an extended (FPU) frame.
+#[cfg(any(has_fpu, not(cortex_m)))]
+const EXC_RETURN_FTYPE: u32 = 1 << 4;
+
+// Layout of a saved thread context, in words from the bottom of the context.
+// The software-saved part comes first and is followed by the frame stacked by the hardware.
+// The per-thread state that is not part of the register file is saved below r4.
+const CTX_CONTROL: usize = 0;
+#[cfg(armv8m_main)]
+const CTX_PSPLIM: usize = 1;
+#[cfg(armv8m_main)]
+const CTX_STATE_WORDS: usize = 2;
+#[cfg(not(armv8m_main))]
+const CTX_STATE_WORDS: usize = 1;
+const CTX_R4: usize = CTX_STATE_WORDS;
+const CTX_EXC_RETURN: usize = CTX_R4 + 8;
+const CTX_SW_WORDS: usize = CTX_EXC_RETURN + 1;
+#[cfg(any(has_fpu, not(cortex_m)))]
+const CTX_SW_FPU_WORDS: usize = 16;
+
+// Layout of the hardware-stacked frame, in words from the bottom of the frame.
+const HW_R0: usize = 0;
+const HW_R1: usize = 1;
+const HW_R2: usize = 2;
+const HW_R3: usize = 3;
+const HW_R12: usize = 4;
+const HW_LR: usize = 5;
+const HW_PC: usize = 6;
+const HW_XPSR: usize = 7;
+const HW_WORDS: usize = 8;
+#[cfg(any(has_fpu, not(cortex_m)))]
+const HW_FPU_WORDS: usize = 18;
+
+/// Pattern an empty stack is painted with, to detect overflows and measure its usage.
+const STACK_PAINT: u32 = 0xA5A5_A5A5;
+
+/// Number of words at the bottom of the stack that are reserved as canary.
+/// ARMv8-M Mainline checks the stack limit in hardware (PSPLIM), so no canary is needed.
+#[cfg(armv8m_main)]
+const STACK_CANARY_WORDS: usize = 0;
+#[cfg(not(armv8m_main))]
+const STACK_CANARY_WORDS: usize = 2;
+
+/// Errors that can occur while creating a thread context.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The stack is too small to hold the initial context of the thread.
+    StackTooSmall,
+}
+
+/// The kind of exception frame a thread context is saved in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FrameKind {
+    /// r0-r3, r12, lr, pc and xPSR are stacked by the hardware.
+    Basic,
+    /// Additionally to the basic frame, s0-s15 and FPSCR are stacked by the hardware and
+    /// s16-s31 are saved by software.
+    ///
+    /// Only available on targets with an FPU, and on the host.
+    #[cfg(any(has_fpu, not(cortex_m)))]
+    Extended,
+}
+
+impl FrameKind {
+    /// Returns the frame kind encoded in an EXC_RETURN value.
+    #[inline]
+    pub fn from_exc_return(exc_return: u32) -> Self {
+        match () {
+            #[cfg(any(has_fpu, not(cortex_m)))]
+            () if exc_return & EXC_RETURN_FTYPE == 0 => FrameKind::Extended,
+            // Without an FPU, the hardware only stacks basic frames.
+            () => {
+                let _ = exc_return;
+                FrameKind::Basic
+            }
+        }
+    }
+
+    /// Returns the EXC_RETURN value to return to Thread mode (using PSP) with this frame kind.
+    #[inline]
+    pub fn exc_return(self) -> u32 {
+        match self {
+            FrameKind::Basic => EXC_RETURN_THREAD_PSP,
+            #[cfg(any(has_fpu, not(cortex_m)))]
+            FrameKind::Extended => EXC_RETURN_THREAD_PSP_FPU,
+        }
+    }
+
+    /// Returns the number of words saved by software, including the EXC_RETURN value.
+    #[inline]
+    pub fn sw_words(self) -> usize {
+        match self {
+            FrameKind::Basic => CTX_SW_WORDS,
+            #[cfg(any(has_fpu, not(cortex_m)))]
+            FrameKind::Extended => CTX_SW_WORDS + CTX_SW_FPU_WORDS,
+        }
+    }
+
+    /// Returns the number of words stacked by the hardware on exception entry.
+    #[inline]
+    pub fn hw_words(self) -> usize {
+        match self {
+            FrameKind::Basic => HW_WORDS,
+            #[cfg(any(has_fpu, not(cortex_m)))]
+            FrameKind::Extended => HW_WORDS + HW_FPU_WORDS,
+        }
+    }
+
+    /// Returns the total number of words of a thread context with this frame kind.
+    #[inline]
+    pub fn words(self) -> usize {
+        self.sw_words() + self.hw_words()
+    }
+}
+
+/// A register saved in a thread context.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Reg {
+    /// r0
+    R0,
+    /// r1
+    R1,
+    /// r2
+    R2,
+    /// r3
+    R3,
+    /// r4
+    R4,
+    /// r5
+    R5,
+    /// r6
+    R6,
+    /// r7
+    R7,
+    /// r8
+    R8,
+    /// r9
+    R9,
+    /// r10
+    R10,
+    /// r11
+    R11,
+    /// r12
+    R12,
+    /// Link register of the thread.
+    Lr,
+    /// Program counter of the thread.
+    Pc,
+    /// Program status register of the thread.
+    Xpsr,
+    /// EXC_RETURN value the context was saved with.
+    ExcReturn,
+}
+
+impl Reg {
+    /// All registers, in the order they are usually printed.
+    pub const ALL: [Reg; 17] = [
+        Reg::R0,
+        Reg::R1,
+        Reg::R2,
+        Reg::R3,
+        Reg::R4,
+        Reg::R5,
+        Reg::R6,
+        Reg::R7,
+        Reg::R8,
+        Reg::R9,
+        Reg::R10,
+        Reg::R11,
+        Reg::R12,
+        Reg::Lr,
+        Reg::Pc,
+        Reg::Xpsr,
+        Reg::ExcReturn,
+    ];
+
+    /// Returns the name of the register.
+    #[inline]
+    pub fn name(self) -> &'static str {
+        match self {
+            Reg::R0 => "r0",
+            Reg::R1 => "r1",
+            Reg::R2 => "r2",
+            Reg::R3 => "r3",
+            Reg::R4 => "r4",
+            Reg::R5 => "r5",
+            Reg::R6 => "r6",
+            Reg::R7 => "r7",
+            Reg::R8 => "r8",
+            Reg::R9 => "r9",
+            Reg::R10 => "r10",
+            Reg::R11 => "r11",
+            Reg::R12 => "r12",
+            Reg::Lr => "lr",
+            Reg::Pc => "pc",
+            Reg::Xpsr => "xpsr",
+            Reg::ExcReturn => "exc_return",
+        }
+    }
+
+    /// Returns the offset of the register in words from the bottom of a context of kind `kind`.
+    #[inline]
+    fn offset(self, kind: FrameKind) -> usize {
+        let hw = kind.sw_words();
+        match self {
+            Reg::R0 => hw + HW_R0,
+            Reg::R1 => hw + HW_R1,
+            Reg::R2 => hw + HW_R2,
+            Reg::R3 => hw + HW_R3,
+            Reg::R4 => CTX_R4,
+            Reg::R5 => CTX_R4 + 1,
+            Reg::R6 => CTX_R4 + 2,
+            Reg::R7 => CTX_R4 + 3,
+            Reg::R8 => CTX_R4 + 4,
+            Reg::R9 => CTX_R4 + 5,
+            Reg::R10 => CTX_R4 + 6,
+            Reg::R11 => CTX_R4 + 7,
+            Reg::R12 => hw + HW_R12,
+            Reg::Lr => hw + HW_LR,
+            Reg::Pc => hw + HW_PC,
+            Reg::Xpsr => hw + HW_XPSR,
+            Reg::ExcReturn => CTX_EXC_RETURN,
+        }
+    }
+}
+
+/// Struct: Stack
+///
+/// The memory region a thread runs on. The stack grows downwards, from `top` to `base`.
+///
+/// On ARMv8-M Mainline the bottom of the stack is enforced as limit by PSPLIM. On other targets,
+/// the lowest words of the stack are used as canary, which can be checked by [`Stack::overflowed`].
+#[derive(Clone, Copy, Debug)]
+pub struct Stack {
+    base: *mut u8,
+    size: usize,
+}
+
+impl Stack {
+    /// Function: new
+    ///
+    /// Precondition: base points to the lowest address of a memory region of `size` bytes.
+    /// The region is exclusively used as stack of a single thread and outlives the thread.
+    ///
+    /// Postcondition: A Stack object is returned.
+    #[inline]
+    pub unsafe fn new(base: *mut u8, size: usize) -> Self {
+        Stack { base, size }
+    }
+
+    /// Returns the lowest address of the stack.
+    #[inline]
+    pub fn base(&self) -> *mut u8 {
+        self.base
+    }
+
+    /// Returns the size of the stack in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the address one past the highest address of the stack.
+    #[inline]
+    pub fn top(&self) -> *mut u8 {
+        self.base.wrapping_add(self.size)
+    }
+
+    /// Returns the lowest address the stack pointer of the thread may reach.
+    /// The limit is 8-byte aligned, as required by PSPLIM.
+    #[inline]
+    pub fn limit(&self) -> *mut u8 {
+        let base = (self.base as usize + 0x7) & !0x7;
+        (base + STACK_CANARY_WORDS * size_of::<u32>()) as *mut u8
+    }
+
+    /// Returns whether the canary at the bottom of the stack has been overwritten.
+    /// Always returns `false` if the stack limit is checked in hardware.
+    ///
+    /// The stack must have been initialized by [`ThreadContext::from_empty`].
+    #[inline]
+    pub fn overflowed(&self) -> bool {
+        match () {
+            #[cfg(armv8m_main)]
+            () => false,
+            #[cfg(not(armv8m_main))]
+            () => {
+                let canary = self.limit() as *const u32;
+                // Safety: The canary words lie within the stack, by contract of `new`.
+                (1..=STACK_CANARY_WORDS)
+                    .any(|i| unsafe { canary.sub(i).read_volatile() } != STACK_PAINT)
+            }
+        }
+    }
+
+    /// Returns the maximum number of bytes the thread has used of its stack so far.
+    ///
+    /// The stack must have been initialized by [`ThreadContext::from_empty`].
+    /// The mark is measured by looking for the first word, from the bottom, that is no longer
+    /// painted. It can therefore underestimate the usage if the thread has written the paint
+    /// pattern itself.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn high_water_mark(&self) -> usize {
+        let limit = self.limit() as *const u32;
+        let top = (self.top() as usize & !0x7) as *const u32;
+
+        if self.overflowed() {
+            return self.size;
+        }
+
+        let mut word = limit;
+        // Safety: All words between limit and top lie within the stack, by contract of `new`.
+        while word < top && unsafe { word.read_volatile() } == STACK_PAINT {
+            word = word.wrapping_add(1);
+        }
+
+        top as usize - word as usize
+    }
+
+    /// Paints the whole stack, including the canary, with the paint pattern.
+    ///
+    /// # Safety
+    /// Preconditions: The stack is not in use.
+    /// Postconditions: Every word of the stack holds the paint pattern.
+    unsafe fn paint(&self) {
+        let mut word = ((self.base as usize + 0x3) & !0x3) as *mut u32;
+        let top = (self.top() as usize & !0x3) as *mut u32;
+
+        while word < top {
+            word.write_volatile(STACK_PAINT);
+            word = word.add(1);
+        }
+    }
+}
+
+/// Struct: ThreadAttrs
+///
+/// The attributes of a thread that are independent of its entry point.
+pub struct ThreadAttrs {
+    /// The identifier of the thread, passed to the finalizer.
+    pub id: usize,
+
+    /// The finalizer function to call when the thread is done.
+    /// It is called in thread mode, on the stack of the thread, with the value returned by the
+    /// entry point. Unprivileged threads call it unprivileged.
+    pub finalizer: extern "C" fn(exit: &ThreadExit) -> !,
+
+    /// The kind of frame the thread is started with.
+    /// Threads that use the FPU can start with either kind, the hardware creates an
+    /// extended frame as soon as the thread has executed a floating point instruction.
+    pub frame: FrameKind,
+
+    /// The stack the thread runs on.
+    pub stack: Stack,
+
+    /// The privilege level the thread runs with in thread mode.
+    /// Unprivileged threads can only be isolated from each other with a configured MPU.
+    pub privilege: Npriv,
+}
+
+/// Struct: ThreadDesc
+pub struct ThreadDesc {
+    /// The number of arguments passed to the thread.
+    pub argc: usize,
+
+    /// The arguments passed to the thread.
+    pub argv: *const *const u8,
+
+    /// The entry point of the thread.
+    pub entry: extern "C" fn(argc: usize, argv: *const *const u8) -> usize,
+
+    /// The attributes of the thread.
+    pub attrs: ThreadAttrs,
+}
+
+/// Struct: ThreadContext
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadContext {
+    ptr: CtxPtr,
+}
+
+impl ThreadContext {
+    /// Function: new
+    ///
+    /// Precondition: ptr is a valid pointer to a thread context.
+    /// Especially ptr must satisfy the following conditions:
+    /// - It points to the bottom of the stack of the corresponding thread.
+    /// - The stack must be 4-byte aligned.
+    /// - The layout of the stack must be as follows (from bottom to top):
+    ///   CONTROL, (if ARMv8-M Mainline -> PSPLIM), r4-r11, EXC_RETURN, (if extended -> s16-s31),
+    ///   r0-r3, r12, lr, pc, xPSR, (if extended -> s0-s15, FPSCR, reserved)
+    ///
+    /// Postcondition: A ThreadContext object is returned.
+    #[inline]
+    pub unsafe fn new(ctx: CtxPtr) -> Self {
+        ThreadContext { ptr: ctx }
+    }
+
+    /// Function: from_empty
+    ///
+    /// Precondition: desc.attrs.stack describes the stack of the thread.
+    /// Especially the stack must satisfy the following conditions:
+    /// - The stack must be empty.
+    /// - The stack must not be used by any other thread.
+    ///
+    /// Postcondition: If the stack is large enough to hold all the registers, a ThreadContext object
+    /// is returned. Otherwise `Error::StackTooSmall` is returned and the stack is left untouched.
+    /// The stack is painted, to be able to measure its high-water mark, and initialized with
+    /// the default values for the registers. When the entry point returns, the finalizer is
+    /// called with the [`ThreadExit`] record stored at the top of the stack.
+    /// The stack pointer can be safely used as a return value for an exception handler.
+    #[inline]
+    pub unsafe fn from_empty(desc: ThreadDesc) -> Result<Self, Error> {
+        // The stack has to contain all the caller-saved registers.
+        // The layout is as follows:
+        // ThreadExit (exit record, 8-byte aligned)
+        // (if extended) reserved, FPSCR (0), S15 - S0 (0)
+        // xPSR
+        // PC (entry point)
+        // LR (exit trampoline)
+        // R12 (scratch register)
+        // R3 (argument to the function - 0)
+        // R2 (argument to the function - 0)
+        // R1 (argument to the function - argv)
+        // R0 (argument to the function - argc)
+        // (if extended) S31 - S16 (0)
+        // LR (EXEC_RETURN)
+        // R11 - R4 (scratch - 0)
+        // (if ARMv8-M Mainline) PSPLIM (stack limit)
+        // CONTROL (privilege level)
+
+        let attrs = desc.attrs;
+        let kind = attrs.frame;
+        let stack = attrs.stack;
+
+        // The hardware frame has to be 8-byte aligned.
+        let top = stack.top() as usize & !0x7;
+        let limit = stack.limit() as usize;
+        let record_size = (size_of::<ThreadExit>() + 0x7) & !0x7;
+        if top < limit || top - limit < record_size + kind.words() * size_of::<u32>() {
+            return Err(Error::StackTooSmall);
+        }
+
+        stack.paint();
+
+        // Store the exit record above the frame, where the stack pointer points to when the
+        // entry point returns.
+        let record = (top - record_size) as *mut ThreadExit;
+        record.write(ThreadExit {
+            id: attrs.id,
+            argc: desc.argc,
+            argv: desc.argv,
+            ret: 0,
+            finalizer: attrs.finalizer,
+        });
+
+        let ctx = (record as *mut u32).sub(kind.words());
+        let hw = ctx.add(kind.sw_words());
+
+        // Set all registers to 0, including the floating point registers and FPSCR.
+        core::ptr::write_bytes(ctx, 0, kind.words());
+
+        // Set the LR register to return to thread mode, using PSP and the frame kind.
+        ctx.add(CTX_EXC_RETURN).write(kind.exc_return());
+
+        // Set the R0 register to argc and the R1 register to argv.
+        hw.add(HW_R0).write(desc.argc as u32);
+        hw.add(HW_R1).write(desc.argv as u32);
+
+        // Set the LR register to the exit trampoline, which calls the finalizer after the thread is done.
+        hw.add(HW_LR).write(exit::trampoline() as u32);
+
+        // Set the PC register to the entry point of the thread. The stacked PC must be halfword
+        // aligned, the Thumb bit of the function address is carried by xPSR instead.
+        hw.add(HW_PC).write(desc.entry as usize as u32 & !1);
+
+        // Set the xPSR register to the default value. (Only the thumb-state bit is set)
+        hw.add(HW_XPSR).write(1 << 24);
+
+        // Set the privilege level of the thread, which runs on PSP.
+        let mut control = Control::from_bits(0);
+        control.set_npriv(attrs.privilege);
+        control.set_spsel(Spsel::Psp);
+        ctx.add(CTX_CONTROL).write(control.bits());
+
+        // Set the stack limit of the thread.
+        #[cfg(armv8m_main)]
+        ctx.add(CTX_PSPLIM).write(limit as u32);
+
+        Ok(Self { ptr: ctx as CtxPtr })
+    }
+
+    /// Function: from_fn
+    ///
+    /// Precondition: attrs.stack describes the stack of the thread, see `from_empty`.
+    ///
+    /// Postcondition: If the stack is large enough to hold `f` and all the registers, a
+    /// ThreadContext object is returned. Otherwise `Error::StackTooSmall` is returned.
+    /// `f` is moved to the top of the stack and called by the thread. The value returned by `f`
+    /// is passed to the finalizer.
+    #[inline]
+    pub unsafe fn from_fn<F>(attrs: ThreadAttrs, f: F) -> Result<Self, Error>
+    where
+        F: FnOnce() -> usize + Send + 'static,
+    {
+        /// Entry point of threads created by `from_fn`, argv points to the closure.
+        extern "C" fn entry<F>(_argc: usize, argv: *const *const u8) -> usize
+        where
+            F: FnOnce() -> usize,
+        {
+            // Safety: argv points to the closure moved to the stack by `from_fn`, which is only
+            // read once, here.
+            let f = unsafe { (argv as *const F).read() };
+            f()
+        }
+
+        // Place the closure at the top of the stack, above the initial context.
+        let stack = attrs.stack;
+        let align = core::mem::align_of::<F>().max(8);
+        let closure = (stack.top() as usize).wrapping_sub(size_of::<F>()) & !(align - 1);
+        if closure < stack.base() as usize || closure > stack.top() as usize {
+            return Err(Error::StackTooSmall);
+        }
+
+        let ctx = Self::from_empty(ThreadDesc {
+            argc: 0,
+            argv: closure as *const *const u8,
+            entry: entry::<F>,
+            attrs: ThreadAttrs {
+                stack: Stack::new(stack.base(), closure - stack.base() as usize),
+                ..attrs
+            },
+        })?;
+
+        (closure as *mut F).write(f);
+        Ok(ctx)
+    }
+
+    /// Function: from_fn_with
+    ///
+    /// Precondition: attrs.stack describes the stack of the thread, see `from_empty`.
+    ///
+    /// Postcondition: If the stack is large enough to hold `arg` and all the registers, a
+    /// ThreadContext object is returned. Otherwise `Error::StackTooSmall` is returned.
+    /// `arg` is moved to the top of the stack and `f` is called with it by the thread.
+    #[inline]
+    pub unsafe fn from_fn_with<A>(
+        attrs: ThreadAttrs,
+        f: fn(A) -> usize,
+        arg: A,
+    ) -> Result<Self, Error>
+    where
+        A: Send + 'static,
+    {
+        Self::from_fn(attrs, move || f(arg))
+    }
+
+    /// Returns the EXC_RETURN value the context was saved with.
+    #[inline]
+    pub fn exc_return(&self) -> u32 {
+        // Safety: By contract of `new`, ptr points to a valid thread context.
+        unsafe { self.ptr.add(CTX_EXC_RETURN).read() }
+    }
+
+    /// Returns whether the context was saved with a basic or an extended frame.
+    #[inline]
+    pub fn frame_kind(&self) -> FrameKind {
+        FrameKind::from_exc_return(self.exc_return())
+    }
+
+    /// Returns the address of the hardware-stacked frame.
+    /// This is the value PSP must hold once the software-saved part has been restored.
+    #[inline]
+    pub fn psp(&self) -> CtxPtr {
+        // Safety: By contract of `new`, the context extends at least to the hardware frame.
+        unsafe { self.ptr.add(self.frame_kind().sw_words()) }
+    }
+
+    /// Returns the privilege level the thread runs with in thread mode.
+    #[inline]
+    pub fn privilege(&self) -> Npriv {
+        // Safety: By contract of `new`, ptr points to a valid thread context.
+        Control::from_bits(unsafe { self.ptr.add(CTX_CONTROL).read() }).npriv()
+    }
+
+    /// Function: save_state
+    ///
+    /// Precondition: Called in handler mode, while switching away from the thread of the context,
+    /// after the context has been saved.
+    ///
+    /// Postcondition: The per-thread state that is not part of the exception frame (CONTROL and,
+    /// on ARMv8-M Mainline, PSPLIM) is stored in the context.
+    #[cfg(cortex_m)]
+    #[inline]
+    pub unsafe fn save_state(&self) {
+        (self.ptr as *mut u32)
+            .add(CTX_CONTROL)
+            .write(register::control::read().bits());
+
+        #[cfg(armv8m_main)]
+        (self.ptr as *mut u32)
+            .add(CTX_PSPLIM)
+            .write(register::psplim::read());
+    }
+
+    /// Function: restore_state
+    ///
+    /// Precondition: Called in handler mode, while switching to the thread of the context,
+    /// before PSP is set to the context.
+    ///
+    /// Postcondition: The per-thread state that is not part of the exception frame is restored
+    /// from the context. The thread mode privilege level (CONTROL.nPRIV) is set to the one of the
+    /// thread and, on ARMv8-M Mainline, PSPLIM is set to the stack limit of the thread.
+    #[cfg(cortex_m)]
+    #[inline]
+    pub unsafe fn restore_state(&self) {
+        // Only nPRIV is per-thread. SPSEL is fixed by EXC_RETURN and FPCA is restored by the hardware.
+        let mut control = register::control::read();
+        control.set_npriv(self.privilege());
+        register::control::write(control);
+
+        #[cfg(armv8m_main)]
+        register::psplim::write(self.ptr.add(CTX_PSPLIM).read());
+    }
+
+    /// Function: registers
+    ///
+    /// Precondition: The thread of the context is suspended and stays suspended while the
+    /// returned view is alive. No other view of the same context exists.
+    ///
+    /// Postcondition: A view over the saved registers of the thread is returned.
+    /// Changes made through the view take effect when the thread is resumed.
+    #[inline]
+    pub unsafe fn registers(&mut self) -> Registers<'_> {
+        Registers {
+            ptr: self.ptr as *mut u32,
+            kind: self.frame_kind(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Struct: Registers
+///
+/// A view over the registers saved in a [`ThreadContext`].
+pub struct Registers<'a> {
+    ptr: *mut u32,
+    kind: FrameKind,
+    marker: PhantomData<&'a mut ThreadContext>,
+}
+
+impl Registers<'_> {
+    /// Returns the saved value of register `reg`.
+    #[inline]
+    pub fn get(&self, reg: Reg) -> u32 {
+        // Safety: By contract of `ThreadContext::registers`, ptr points to a suspended context of kind `kind`.
+        unsafe { self.ptr.add(reg.offset(self.kind)).read() }
+    }
+
+    /// Sets the saved value of register `reg`.
+    ///
+    /// Note that changing the EXC_RETURN value may change the kind of the frame, which
+    /// invalidates the context.
+    #[inline]
+    pub fn set(&mut self, reg: Reg, value: u32) {
+        // Safety: By contract of `ThreadContext::registers`, ptr points to a suspended context of kind `kind`
+        // and we are the only ones accessing it.
+        unsafe { self.ptr.add(reg.offset(self.kind)).write(value) }
+    }
+
+    /// Returns the `n`-th argument register (r0-r3) of the thread.
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than 3.
+    #[inline]
+    pub fn arg(&self, n: usize) -> u32 {
+        self.get(Reg::ALL[..4][n])
+    }
+
+    /// Sets the return value register (r0) of the thread.
+    #[inline]
+    pub fn set_ret(&mut self, value: u32) {
+        self.set(Reg::R0, value)
+    }
+
+    /// Returns the kind of frame the registers are saved in.
+    #[inline]
+    pub fn frame_kind(&self) -> FrameKind {
+        self.kind
+    }
+}
+
+impl fmt::Debug for Registers<'_> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut dbg = f.debug_struct("Registers");
+        for reg in Reg::ALL {
+            dbg.field(reg.name(), &format_args!("{:#010x}", self.get(reg)));
+        }
+        dbg.field("frame", &self.kind).finish()
+    }
+}
+
+impl From<CtxPtr> for ThreadContext {
+    #[inline]
+    // The conversion itself does not dereference the pointer received.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn from(ctx: CtxPtr) -> Self {
+        unsafe { ThreadContext::new(ctx) }
+    }
+}
+
+impl From<ThreadContext> for CtxPtr {
+    #[inline]
+    fn from(ctx: ThreadContext) -> Self {
+        ctx.ptr
+    }
+}
+
+/// Reschedule the tasks.
+#[inline]
+pub fn reschedule() {
+    // Call PendSV to reschedule the tasks.
+    peripheral::SCB::set_pendsv();
+}
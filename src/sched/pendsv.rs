@@ -0,0 +1,204 @@
+//! PendSV context switch handler
+//!
+//! With the `sched-pendsv` feature enabled, this module provides the `PendSV` exception handler.
+//! The handler saves the context of the running thread, calls the scheduler registered with
+//! [`set_scheduler`] and restores the context returned by it. The layout of the contexts is
+//! described in [`ThreadContext::new`](super::ThreadContext::new).
+//!
+//! The handler must run at the lowest exception priority, so that it never preempts another
+//! exception handler.
+
+#[cfg(cortex_m)]
+use core::arch::global_asm;
+
+use super::CtxPtr;
+#[cfg(cortex_m)]
+use super::ThreadContext;
+#[cfg(any(cortex_m, test))]
+use super::CTX_STATE_WORDS;
+use crate::sync::OnceCell;
+
+/// The scheduler called by the PendSV handler.
+static SCHEDULER: OnceCell<extern "C" fn(CtxPtr) -> CtxPtr> = OnceCell::new();
+
+/// Registers the scheduler called by the PendSV handler.
+///
+/// The scheduler receives the context of the thread that was interrupted and returns the context
+/// of the thread to resume. The interrupted context is null if PendSV was taken from thread mode
+/// using MSP, e.g. to start the first thread. If the scheduler returns null, the handler returns
+/// to the interrupted context without switching.
+///
+/// Returns `false` if a scheduler was already registered.
+#[inline]
+pub fn set_scheduler(scheduler: extern "C" fn(CtxPtr) -> CtxPtr) -> bool {
+    SCHEDULER.set(scheduler).is_some()
+}
+
+/// Called by the PendSV handler, after the registers of the interrupted thread have been saved.
+///
+/// `regs` points to the saved r4 of the interrupted thread, or is null if there is no
+/// interrupted thread. Returns a pointer to the saved r4 of the thread to resume, or null to
+/// return to the interrupted context, whose registers are still live, without restoring anything.
+///
+/// Compiled natively for the tests, without the parts that access the processor state.
+#[cfg(any(cortex_m, test))]
+pub(super) unsafe extern "C" fn switch(regs: *mut u32) -> *const u32 {
+    let prev: CtxPtr = if regs.is_null() {
+        core::ptr::null()
+    } else {
+        let prev = regs.sub(CTX_STATE_WORDS);
+        #[cfg(cortex_m)]
+        ThreadContext::new(prev).save_state();
+        prev
+    };
+
+    // A thread switched out between LDREX and STREX must not complete its STREX with the
+    // exclusive access of another thread.
+    #[cfg(all(cortex_m, not(armv6m)))]
+    crate::asm::clrex();
+
+    // Without a scheduler the interrupted thread is resumed.
+    let next = match SCHEDULER.get() {
+        Some(scheduler) => scheduler(prev),
+        None => prev,
+    };
+
+    // Taken from MSP without a thread to switch to, e.g. before the scheduler is registered.
+    if next.is_null() {
+        return core::ptr::null();
+    }
+
+    #[cfg(cortex_m)]
+    ThreadContext::new(next).restore_state();
+    next.add(CTX_STATE_WORDS)
+}
+
+// ARMv7-M and ARMv8-M Mainline, without FPU.
+#[cfg(all(any(armv7m, armv8m_main), not(has_fpu)))]
+global_asm!(
+    ".section .text.PendSV, \"ax\", %progbits",
+    ".global PendSV",
+    ".type PendSV, %function",
+    ".thumb_func",
+    "PendSV:",
+    // Only threads run on PSP, there is nothing to save if PendSV was taken from MSP.
+    "    tst lr, #4",
+    "    beq 1f",
+    "    mrs r0, psp",
+    "    stmdb r0!, {{r4-r11, lr}}",
+    "    b 2f",
+    "1:",
+    "    movs r0, #0",
+    "2:",
+    // Keep EXC_RETURN to return to the interrupted context if there is nothing to restore.
+    "    push {{r0, lr}}",
+    "    bl {switch}",
+    "    pop {{r1, lr}}",
+    "    cbz r0, 3f",
+    "    ldmia r0!, {{r4-r11, lr}}",
+    "    msr psp, r0",
+    "3:",
+    "    bx lr",
+    ".size PendSV, . - PendSV",
+    switch = sym switch,
+);
+
+// ARMv7E-M and ARMv8-M Mainline, with FPU.
+// s16-s31 are only saved and restored if the thread has an extended frame (EXC_RETURN bit 4 clear).
+// Module level assembly doesn't inherit the target features, so the FPU has to be enabled explicitly.
+// FPv4-SP is the baseline of both architectures: the FPv5 of ARMv8-M Mainline encodes the
+// `vstmdb`/`vldmia` of s16-s31 the same way, and nothing else here uses the FPU.
+#[cfg(all(any(armv7m, armv8m_main), has_fpu))]
+global_asm!(
+    ".fpu fpv4-sp-d16",
+    ".section .text.PendSV, \"ax\", %progbits",
+    ".global PendSV",
+    ".type PendSV, %function",
+    ".thumb_func",
+    "PendSV:",
+    // Only threads run on PSP, there is nothing to save if PendSV was taken from MSP.
+    "    tst lr, #4",
+    "    beq 1f",
+    "    mrs r0, psp",
+    "    tst lr, #0x10",
+    "    it eq",
+    "    vstmdbeq r0!, {{s16-s31}}",
+    "    stmdb r0!, {{r4-r11, lr}}",
+    "    b 2f",
+    "1:",
+    "    movs r0, #0",
+    "2:",
+    // Keep EXC_RETURN to return to the interrupted context if there is nothing to restore.
+    "    push {{r0, lr}}",
+    "    bl {switch}",
+    "    pop {{r1, lr}}",
+    "    cbz r0, 3f",
+    "    ldmia r0!, {{r4-r11, lr}}",
+    "    tst lr, #0x10",
+    "    it eq",
+    "    vldmiaeq r0!, {{s16-s31}}",
+    "    msr psp, r0",
+    "3:",
+    "    bx lr",
+    ".size PendSV, . - PendSV",
+    switch = sym switch,
+);
+
+// ARMv6-M and ARMv8-M Baseline.
+// These can only store and load r0-r7 with stm/ldm, so r8-r11 are moved through r4-r7.
+#[cfg(any(armv6m, armv8m_base))]
+global_asm!(
+    ".section .text.PendSV, \"ax\", %progbits",
+    ".global PendSV",
+    ".type PendSV, %function",
+    ".thumb_func",
+    "PendSV:",
+    // Only threads run on PSP, there is nothing to save if PendSV was taken from MSP.
+    "    mov r0, lr",
+    "    movs r1, #4",
+    "    tst r0, r1",
+    "    beq 1f",
+    "    mrs r0, psp",
+    "    subs r0, #36",
+    "    stmia r0!, {{r4-r7}}",
+    "    mov r4, r8",
+    "    mov r5, r9",
+    "    mov r6, r10",
+    "    mov r7, r11",
+    "    stmia r0!, {{r4-r7}}",
+    "    mov r1, lr",
+    "    str r1, [r0]",
+    "    subs r0, #32",
+    "    b 2f",
+    "1:",
+    "    movs r0, #0",
+    "2:",
+    // Keep EXC_RETURN to return to the interrupted context if there is nothing to restore.
+    "    push {{r0, lr}}",
+    "    bl {switch}",
+    "    pop {{r1, r2}}",
+    "    cmp r0, #0",
+    "    beq 3f",
+    "    adds r0, #16",
+    "    ldmia r0!, {{r4-r7}}",
+    "    mov r8, r4",
+    "    mov r9, r5",
+    "    mov r10, r6",
+    "    mov r11, r7",
+    "    ldr r1, [r0]",
+    "    adds r0, #4",
+    "    msr psp, r0",
+    "    subs r0, #36",
+    "    ldmia r0!, {{r4-r7}}",
+    "    bx r1",
+    // Nothing to switch to. If a thread was interrupted, its r4-r7 were overwritten with r8-r11
+    // while saving them and are reloaded from its context.
+    "3:",
+    "    cmp r1, #0",
+    "    beq 4f",
+    "    ldmia r1!, {{r4-r7}}",
+    "4:",
+    "    bx r2",
+    ".size PendSV, . - PendSV",
+    switch = sym switch,
+);
@@ -0,0 +1,186 @@
+//! Host-side simulation of thread contexts
+//!
+//! Applies the semantics of an exception return to a [`ThreadContext`] in software, the way the
+//! PendSV handler and the hardware do on the target, and the inverse, saving a [`Cpu`] state as
+//! context. This allows to test code that builds or modifies thread contexts natively.
+//!
+//! Only available when compiling for the host (`cfg(native)`).
+
+use super::{
+    CtxPtr, FrameKind, ThreadContext, CTX_CONTROL, CTX_EXC_RETURN, CTX_R4, CTX_STATE_WORDS,
+    EXC_RETURN_THREAD_PSP, EXC_RETURN_THREAD_PSP_FPU, HW_LR, HW_PC, HW_R0, HW_R12, HW_WORDS,
+    HW_XPSR,
+};
+use crate::register::control::Control;
+
+/// xPSR bit that is set if the frame was padded to 8-byte alignment.
+const XPSR_ALIGN: u32 = 1 << 9;
+
+/// xPSR bit that selects the Thumb state.
+const XPSR_THUMB: u32 = 1 << 24;
+
+/// The state of the simulated processor in thread mode.
+#[derive(Clone, Copy, Debug)]
+#[allow(clippy::missing_inline_in_public_items)]
+pub struct Cpu {
+    /// r0-r12
+    pub r: [u32; 13],
+    /// The process stack pointer.
+    pub sp: *mut u32,
+    /// The link register.
+    pub lr: u32,
+    /// The program counter.
+    pub pc: u32,
+    /// The program status register.
+    pub xpsr: u32,
+    /// s0-s31, only restored from and saved to extended frames.
+    pub s: [u32; 32],
+    /// The floating point status and control register, only restored from and saved to
+    /// extended frames.
+    pub fpscr: u32,
+    /// The CONTROL register. Only nPRIV is part of the thread context.
+    pub control: Control,
+    /// The kind of frame the state was restored from, and is saved to.
+    pub frame: FrameKind,
+}
+
+/// Faults the processor raises on an invalid exception return.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// EXC_RETURN is not a return to thread mode using PSP (INVPC).
+    InvalidExcReturn(u32),
+    /// The Thumb bit is cleared in the stacked xPSR (INVSTATE).
+    InvalidState(u32),
+    /// The stacked PC is not halfword aligned, i.e. bit 0 is set (UNPREDICTABLE).
+    UnalignedPc(u32),
+}
+
+/// Function: exception_return
+///
+/// Precondition: ctx is a valid thread context, see `ThreadContext::new`.
+///
+/// Postcondition: The context is popped, as the PendSV handler and the hardware do on exception
+/// return, and the resulting processor state is returned. If the hardware would fault, the
+/// fault is returned instead.
+#[allow(clippy::missing_inline_in_public_items)]
+pub unsafe fn exception_return(ctx: ThreadContext) -> Result<Cpu, Fault> {
+    let ptr = CtxPtr::from(ctx);
+
+    let exc_return = ptr.add(CTX_EXC_RETURN).read();
+    if exc_return != EXC_RETURN_THREAD_PSP && exc_return != EXC_RETURN_THREAD_PSP_FPU {
+        return Err(Fault::InvalidExcReturn(exc_return));
+    }
+    let frame = FrameKind::from_exc_return(exc_return);
+
+    let mut cpu = Cpu {
+        r: [0; 13],
+        sp: core::ptr::null_mut(),
+        lr: 0,
+        pc: 0,
+        xpsr: 0,
+        s: [0; 32],
+        fpscr: 0,
+        control: Control::from_bits(0),
+        frame,
+    };
+
+    // The software-saved part, restored by the PendSV handler.
+    cpu.control.set_npriv(ctx.privilege());
+    for i in 0..8 {
+        cpu.r[4 + i] = ptr.add(CTX_R4 + i).read();
+    }
+    let mut sp = ptr.add(CTX_EXC_RETURN + 1);
+    if frame == FrameKind::Extended {
+        for i in 0..16 {
+            cpu.s[16 + i] = sp.add(i).read();
+        }
+        sp = sp.add(16);
+    }
+
+    // The hardware-stacked frame, restored on exception return.
+    for i in 0..4 {
+        cpu.r[i] = sp.add(HW_R0 + i).read();
+    }
+    cpu.r[12] = sp.add(HW_R12).read();
+    cpu.lr = sp.add(HW_LR).read();
+    cpu.pc = sp.add(HW_PC).read();
+    cpu.xpsr = sp.add(HW_XPSR).read();
+
+    if frame == FrameKind::Extended {
+        for i in 0..16 {
+            cpu.s[i] = sp.add(HW_WORDS + i).read();
+        }
+        cpu.fpscr = sp.add(HW_WORDS + 16).read();
+    }
+    sp = sp.add(frame.hw_words());
+
+    if cpu.xpsr & XPSR_THUMB == 0 {
+        return Err(Fault::InvalidState(cpu.xpsr));
+    }
+    if cpu.pc & 1 != 0 {
+        return Err(Fault::UnalignedPc(cpu.pc));
+    }
+    if cpu.xpsr & XPSR_ALIGN != 0 {
+        sp = sp.add(1);
+    }
+
+    cpu.xpsr &= !XPSR_ALIGN;
+    cpu.sp = sp as *mut u32;
+    Ok(cpu)
+}
+
+/// Function: exception_entry
+///
+/// Precondition: cpu.sp points into the stack of the thread, with enough room below it to
+/// save the context.
+///
+/// Postcondition: The processor state is pushed on its stack, as the hardware does on exception
+/// entry and the PendSV handler does afterwards, and the saved context is returned.
+#[allow(clippy::missing_inline_in_public_items)]
+pub unsafe fn exception_entry(cpu: &Cpu) -> ThreadContext {
+    let frame = cpu.frame;
+    let mut xpsr = cpu.xpsr & !XPSR_ALIGN;
+
+    // The hardware aligns the frame to 8 bytes.
+    let mut sp = cpu.sp;
+    if sp as usize & 0x7 != 0 {
+        sp = sp.sub(1);
+        xpsr |= XPSR_ALIGN;
+    }
+
+    sp = sp.sub(frame.hw_words());
+    for i in 0..4 {
+        sp.add(HW_R0 + i).write(cpu.r[i]);
+    }
+    sp.add(HW_R12).write(cpu.r[12]);
+    sp.add(HW_LR).write(cpu.lr);
+    sp.add(HW_PC).write(cpu.pc);
+    sp.add(HW_XPSR).write(xpsr);
+
+    if frame == FrameKind::Extended {
+        for i in 0..16 {
+            sp.add(HW_WORDS + i).write(cpu.s[i]);
+        }
+        sp.add(HW_WORDS + 16).write(cpu.fpscr);
+        sp.add(HW_WORDS + 17).write(0);
+
+        sp = sp.sub(16);
+        for i in 0..16 {
+            sp.add(i).write(cpu.s[16 + i]);
+        }
+    }
+
+    sp = sp.sub(CTX_EXC_RETURN + 1);
+    for i in 0..8 {
+        sp.add(CTX_R4 + i).write(cpu.r[4 + i]);
+    }
+    sp.add(CTX_EXC_RETURN).write(frame.exc_return());
+
+    // The per-thread state, saved by `ThreadContext::save_state` on the target.
+    for i in 0..CTX_STATE_WORDS {
+        sp.add(i).write(0);
+    }
+    sp.add(CTX_CONTROL).write(cpu.control.bits());
+
+    ThreadContext::new(sp)
+}
@@ -0,0 +1,263 @@
+use super::sim::{self, Fault};
+use super::{
+    exit, Error, FrameKind, Reg, Stack, ThreadAttrs, ThreadContext, ThreadDesc, ThreadExit,
+    EXC_RETURN_THREAD_PSP, EXC_RETURN_THREAD_PSP_FPU,
+};
+use crate::register::control::Npriv;
+use core::mem::size_of;
+
+#[repr(align(8))]
+struct Mem([u32; 256]);
+
+extern "C" fn entry(_argc: usize, _argv: *const *const u8) -> usize {
+    0
+}
+
+/// The stacked PC of `entry`, halfword aligned.
+fn entry_pc() -> u32 {
+    entry as extern "C" fn(usize, *const *const u8) -> usize as usize as u32 & !1
+}
+
+extern "C" fn finalizer(_exit: &ThreadExit) -> ! {
+    panic!("finalizer called");
+}
+
+fn attrs(mem: &mut Mem, frame: FrameKind) -> ThreadAttrs {
+    ThreadAttrs {
+        id: 7,
+        finalizer,
+        frame,
+        stack: unsafe { Stack::new(mem.0.as_mut_ptr() as *mut u8, size_of::<Mem>()) },
+        privilege: Npriv::Unprivileged,
+    }
+}
+
+fn desc(attrs: ThreadAttrs) -> ThreadDesc {
+    ThreadDesc {
+        argc: 2,
+        argv: 0x2000_0100 as *const *const u8,
+        entry,
+        attrs,
+    }
+}
+
+#[test]
+fn exception_return_basic() {
+    let mut mem = Mem([0; 256]);
+    let attrs = attrs(&mut mem, FrameKind::Basic);
+    let stack = attrs.stack;
+    let ctx = unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap();
+    assert_eq!(ctx.exc_return(), EXC_RETURN_THREAD_PSP);
+
+    let cpu = unsafe { sim::exception_return(ctx) }.unwrap();
+    assert_eq!(cpu.frame, FrameKind::Basic);
+    assert_eq!(cpu.pc, entry_pc());
+    assert_eq!(cpu.lr, exit::trampoline() as u32);
+    assert_eq!(cpu.xpsr, 1 << 24);
+    assert_eq!(cpu.r[0], 2);
+    assert_eq!(cpu.r[1], 0x2000_0100);
+    assert!(cpu.r[2..].iter().all(|&r| r == 0));
+    assert_eq!(cpu.control.npriv(), Npriv::Unprivileged);
+
+    // The thread starts with the exit record on top of its stack.
+    let record = unsafe { &*(cpu.sp as *const ThreadExit) };
+    assert_eq!(
+        cpu.sp as usize + size_of::<ThreadExit>().next_multiple_of(8),
+        stack.top() as usize
+    );
+    assert_eq!(record.id, 7);
+    assert_eq!(record.argc, 2);
+    assert!(!stack.overflowed());
+}
+
+#[test]
+fn exception_return_extended() {
+    let mut mem = Mem([0; 256]);
+    let attrs = attrs(&mut mem, FrameKind::Extended);
+    let top = attrs.stack.top() as usize;
+    let ctx = unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap();
+    assert_eq!(ctx.exc_return(), EXC_RETURN_THREAD_PSP_FPU);
+
+    let cpu = unsafe { sim::exception_return(ctx) }.unwrap();
+    assert_eq!(cpu.frame, FrameKind::Extended);
+    assert_eq!(cpu.pc, entry_pc());
+    assert_eq!(cpu.fpscr, 0);
+    assert!(cpu.s.iter().all(|&s| s == 0));
+    assert_eq!(
+        cpu.sp as usize + size_of::<ThreadExit>().next_multiple_of(8),
+        top
+    );
+}
+
+#[test]
+fn exception_return_registers() {
+    let mut mem = Mem([0; 256]);
+    let attrs = attrs(&mut mem, FrameKind::Extended);
+    let mut ctx = unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap();
+
+    {
+        let mut regs = unsafe { ctx.registers() };
+        for (i, &reg) in Reg::ALL[..13].iter().enumerate() {
+            regs.set(reg, 0x100 + i as u32);
+        }
+        regs.set_ret(0xCAFE);
+    }
+
+    let cpu = unsafe { sim::exception_return(ctx) }.unwrap();
+    assert_eq!(cpu.r[0], 0xCAFE);
+    for i in 1..13 {
+        assert_eq!(cpu.r[i], 0x100 + i as u32);
+    }
+}
+
+#[test]
+fn exception_return_faults() {
+    let mut mem = Mem([0; 256]);
+    let attrs = attrs(&mut mem, FrameKind::Basic);
+    let mut ctx = unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap();
+
+    let mut regs = unsafe { ctx.registers() };
+    regs.set(Reg::Pc, 0x0800_0101);
+    assert_eq!(
+        unsafe { sim::exception_return(ctx) }.unwrap_err(),
+        Fault::UnalignedPc(0x0800_0101)
+    );
+
+    let mut regs = unsafe { ctx.registers() };
+    regs.set(Reg::Pc, 0x0800_0100);
+    regs.set(Reg::Xpsr, 0);
+    assert_eq!(
+        unsafe { sim::exception_return(ctx) }.unwrap_err(),
+        Fault::InvalidState(0)
+    );
+
+    let mut regs = unsafe { ctx.registers() };
+    regs.set(Reg::Xpsr, 1 << 24);
+    regs.set(Reg::ExcReturn, 0xFFFF_FFF9);
+    assert_eq!(
+        unsafe { sim::exception_return(ctx) }.unwrap_err(),
+        Fault::InvalidExcReturn(0xFFFF_FFF9)
+    );
+}
+
+#[test]
+fn exception_round_trip() {
+    for frame in [FrameKind::Basic, FrameKind::Extended] {
+        let mut mem = Mem([0; 256]);
+        let attrs = attrs(&mut mem, frame);
+        let ctx = unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap();
+        let mut cpu = unsafe { sim::exception_return(ctx) }.unwrap();
+
+        // Run the thread for a bit, leaving the stack unaligned.
+        cpu.sp = unsafe { cpu.sp.sub(3) };
+        cpu.r = core::array::from_fn(|i| i as u32 * 3);
+        cpu.s = core::array::from_fn(|i| i as u32 * 5);
+        cpu.pc = 0x0800_0200;
+        cpu.xpsr = 1 << 24 | 0x0B;
+
+        let saved = unsafe { sim::exception_entry(&cpu) };
+        assert_eq!(saved.frame_kind(), frame);
+        assert_eq!(saved.privilege(), Npriv::Unprivileged);
+
+        let resumed = unsafe { sim::exception_return(saved) }.unwrap();
+        assert_eq!(resumed.sp, cpu.sp);
+        assert_eq!(resumed.r, cpu.r);
+        assert_eq!(resumed.pc, cpu.pc);
+        assert_eq!(resumed.xpsr, cpu.xpsr);
+        if frame == FrameKind::Extended {
+            assert_eq!(resumed.s, cpu.s);
+        }
+    }
+}
+
+#[test]
+fn from_fn() {
+    let mut mem = Mem([0; 256]);
+    let attrs = attrs(&mut mem, FrameKind::Basic);
+    let top = attrs.stack.top() as usize;
+    let ctx = unsafe { ThreadContext::from_fn_with(attrs, |x: u64| x as usize, 42) }.unwrap();
+
+    let cpu = unsafe { sim::exception_return(ctx) }.unwrap();
+    assert_eq!(cpu.r[0], 0);
+
+    // The argument is stored above the exit record, at the top of the stack.
+    let record = unsafe { &*(cpu.sp as *const ThreadExit) };
+    let closure = record.argv as usize;
+    assert_eq!(closure as u32, cpu.r[1]);
+    assert!(closure >= cpu.sp as usize + size_of::<ThreadExit>() && closure < top);
+}
+
+#[test]
+fn stack_too_small() {
+    let mut mem = Mem([0; 256]);
+    let mut attrs = attrs(&mut mem, FrameKind::Extended);
+    attrs.stack = unsafe { Stack::new(attrs.stack.base(), 64) };
+    assert_eq!(
+        unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap_err(),
+        Error::StackTooSmall
+    );
+}
+
+#[test]
+fn stack_high_water_mark() {
+    let mut mem = Mem([0; 256]);
+    let attrs = attrs(&mut mem, FrameKind::Basic);
+    let stack = attrs.stack;
+    let ctx = unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap();
+
+    // Only the initial context is used.
+    let used = stack.top() as usize - ctx.ptr as usize;
+    assert_eq!(stack.high_water_mark(), used);
+
+    // The thread wrote 3 words below its initial context.
+    unsafe { (ctx.ptr as *mut u32).sub(3).write(0) };
+    assert_eq!(stack.high_water_mark(), used + 3 * size_of::<u32>());
+    assert!(!stack.overflowed());
+}
+
+#[test]
+fn stack_overflowed() {
+    let mut mem = Mem([0; 256]);
+    let attrs = attrs(&mut mem, FrameKind::Basic);
+    let stack = attrs.stack;
+    unsafe { ThreadContext::from_empty(desc(attrs)) }.unwrap();
+    assert!(!stack.overflowed());
+
+    // The thread overwrote the canary at the bottom of its stack.
+    unsafe { (stack.base() as *mut u32).write(0) };
+    assert!(stack.overflowed());
+    assert_eq!(stack.high_water_mark(), stack.size());
+}
+
+#[cfg(feature = "sched-pendsv")]
+#[test]
+fn pendsv_switch() {
+    use super::pendsv::{set_scheduler, switch};
+    use super::{CtxPtr, CTX_STATE_WORDS};
+    use core::ptr;
+
+    let mut mem = Mem([0; 256]);
+    let ctx =
+        unsafe { ThreadContext::from_empty(desc(attrs(&mut mem, FrameKind::Basic))) }.unwrap();
+    let regs = unsafe { ctx.ptr.add(CTX_STATE_WORDS) } as *mut u32;
+
+    // Taken from MSP without a scheduler: nothing is restored, the handler returns to the
+    // interrupted context.
+    assert!(unsafe { switch(ptr::null_mut()) }.is_null());
+
+    // Without a scheduler the interrupted thread is resumed.
+    let next = unsafe { switch(regs) };
+    assert_eq!(next, regs as *const u32);
+    let cpu = unsafe { sim::exception_return(ThreadContext::new(next.sub(CTX_STATE_WORDS))) };
+    assert_eq!(cpu.unwrap().pc, entry_pc());
+
+    // A scheduler without a thread to run: the handler returns to the interrupted context. The
+    // registers the handler itself moved (r4-r7 on ARMv6-M and ARMv8-M Baseline) are reloaded by
+    // the assembly of the handler, which isn't covered here.
+    extern "C" fn idle(_prev: CtxPtr) -> CtxPtr {
+        ptr::null()
+    }
+    assert!(set_scheduler(idle));
+    assert!(unsafe { switch(ptr::null_mut()) }.is_null());
+    assert!(unsafe { switch(regs) }.is_null());
+}
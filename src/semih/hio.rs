@@ -5,7 +5,7 @@
 
 use core::{ffi::CStr, fmt, slice};
 
-use crate::{pack_args, sync::OnceCell};
+use crate::sync::OnceCell;
 
 use super::{
     debug::{semih_call, SEMIH_OPEN, SEMIH_WRITE, SEMIH_WRITE0},
@@ -62,6 +62,18 @@ pub fn hstdout() -> &'static Result<HostStream, Error> {
     HSTDOUT.do_or_get(|| open(":tt\0", W_TRUNC))
 }
 
+/// Resets the handles to the host's standard output and standard error, which are opened again
+/// on their next use, e.g. after the debugger reconnected.
+///
+/// # Safety
+///
+/// No reference returned by [`hstdout`] or [`hstderr`] may be alive.
+#[inline]
+pub unsafe fn reset() {
+    HSTDOUT.reset();
+    HSTDERR.reset();
+}
+
 fn open(name: &str, mode: usize) -> Result<HostStream, Error> {
     let name = name.as_bytes();
     match unsafe { semih_call(SEMIH_OPEN, &pack_args!(name.as_ptr(), mode, name.len() - 1)) }
@@ -0,0 +1,1362 @@
+//! Synchronization primitives.
+
+use crate::asm;
+use crate::atomic::AtomicBool;
+use crate::atomic::AtomicU32;
+use crate::atomic::AtomicU8;
+use crate::atomic::Ordering;
+use crate::interrupt::{self, CriticalSection};
+use crate::register::primask::Primask;
+use core::cell::UnsafeCell;
+use core::convert::Infallible;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+pub mod spsc;
+
+#[cfg(test)]
+mod test;
+
+/// How a lock waits until it can be acquired.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[allow(clippy::missing_inline_in_public_items)]
+pub enum Wait {
+    /// Busy-wait at full power.
+    Spin,
+    /// Sleep with WFE, and wake up the waiters with SEV when the lock is released.
+    ///
+    /// WFE also returns on any exception entry, so waiting for a lock released by an
+    /// interrupt handler doesn't need an SEV from the handler.
+    Event,
+}
+
+impl Wait {
+    /// Waits once, before checking the lock again.
+    #[inline]
+    fn wait(self) {
+        match self {
+            Wait::Spin => asm::nop(),
+            Wait::Event => wait_for_event(),
+        }
+    }
+
+    /// Wakes up the waiters, after the lock was released.
+    #[inline]
+    fn signal(self) {
+        if self == Wait::Event {
+            send_event();
+        }
+    }
+}
+
+/// A mutual exclusion primitive, facilitating busy-waiting.
+pub struct SpinLock {
+    lock: AtomicBool,
+    wait: Wait,
+}
+
+impl SpinLock {
+    /// Creates a new SpinLock, busy-waiting until it can be acquired.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_wait(Wait::Spin)
+    }
+
+    /// Creates a new SpinLock, waiting as specified by `wait` until it can be acquired.
+    #[inline]
+    pub const fn with_wait(wait: Wait) -> Self {
+        SpinLock {
+            lock: AtomicBool::new(false),
+            wait,
+        }
+    }
+
+    /// Waits until the SpinLock can be acquired and locks it.
+    #[inline]
+    pub fn lock(&self) {
+        let lock = &self.lock;
+
+        while lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Wait with plain loads, until the lock was released.
+            while lock.load(Ordering::Relaxed) {
+                self.wait.wait();
+            }
+        }
+    }
+
+    /// Tries to lock the SpinLock.
+    /// Returns `true` if the lock was acquired.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        !self.lock.swap(true, Ordering::Acquire)
+    }
+
+    /// Unlocks the SpinLock.
+    ///
+    /// # Safety
+    /// Precondition: The SpinLock must be locked by the current thread.
+    /// Postcondition: The SpinLock is unlocked.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        self.lock.store(false, Ordering::Release);
+        self.wait.signal();
+    }
+}
+
+impl Default for SpinLock {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A guard that releases the SpinLock when dropped.
+pub struct SpinLockGuard<'a, T: ?Sized> {
+    lock: &'a SpinLock,
+    value: NonNull<T>,
+    marker: core::marker::PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> core::ops::Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> core::ops::DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.lock.unlock();
+        }
+    }
+}
+
+/// A mutual exclusion primitive that allows at most one thread to access a resource at a time.
+pub struct SpinLocked<T> {
+    lock: SpinLock,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T> Sync for SpinLocked<T> {}
+
+/// Test
+impl<T> SpinLocked<T> {
+    /// Creates a new SpinLocked, busy-waiting until it can be locked.
+    pub const fn new(value: T) -> Self {
+        Self::with_wait(value, Wait::Spin)
+    }
+
+    /// Creates a new SpinLocked, waiting as specified by `wait` until it can be locked.
+    #[inline]
+    pub const fn with_wait(value: T, wait: Wait) -> Self {
+        SpinLocked {
+            lock: SpinLock::with_wait(wait),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Locks the SpinLocked and returns a guard that releases the lock when dropped.
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        self.lock.lock();
+        SpinLockGuard {
+            lock: &self.lock,
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Tries to lock the SpinLocked and returns a guard that releases the lock when dropped.
+    pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        if self.lock.try_lock() {
+            Some(SpinLockGuard {
+                lock: &self.lock,
+                value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                marker: core::marker::PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// A fair mutual exclusion primitive, facilitating waiting for events.
+///
+/// Waiters are served in the order they called [`TicketLock::lock`], so no core can starve
+/// another. While waiting, the core sleeps with WFE and is woken up by the SEV on unlock.
+/// At most 255 contexts can wait for the lock at the same time.
+pub struct TicketLock {
+    next: AtomicU8,
+    serving: AtomicU8,
+}
+
+impl TicketLock {
+    /// Creates a new TicketLock.
+    #[inline]
+    pub const fn new() -> Self {
+        TicketLock {
+            next: AtomicU8::new(0),
+            serving: AtomicU8::new(0),
+        }
+    }
+
+    /// Draws a ticket and waits until it is served.
+    #[inline]
+    pub fn lock(&self) {
+        let ticket = match self
+            .next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+                Some(next.wrapping_add(1))
+            }) {
+            Ok(ticket) | Err(ticket) => ticket,
+        };
+
+        while self.serving.load(Ordering::Acquire) != ticket {
+            wait_for_event();
+        }
+    }
+
+    /// Tries to lock the TicketLock, if no one else is holding or waiting for it.
+    /// Returns `true` if the lock was acquired.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        let serving = self.serving.load(Ordering::Acquire);
+        self.next
+            .compare_exchange(
+                serving,
+                serving.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    /// Unlocks the TicketLock and serves the next ticket.
+    ///
+    /// # Safety
+    /// Precondition: The TicketLock must be locked by the current thread.
+    /// Postcondition: The TicketLock is unlocked.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        // Only the holder of the lock writes `serving`.
+        let next = self.serving.load(Ordering::Relaxed).wrapping_add(1);
+        self.serving.store(next, Ordering::Release);
+        send_event();
+    }
+}
+
+impl Default for TicketLock {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A fair mutual exclusion primitive that allows at most one thread to access a resource at a time.
+pub struct TicketLocked<T> {
+    lock: TicketLock,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for TicketLocked<T> {}
+
+impl<T> TicketLocked<T> {
+    /// Creates a new TicketLocked.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        TicketLocked {
+            lock: TicketLock::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Locks the TicketLocked and returns a guard that releases the lock when dropped.
+    #[inline]
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        self.lock.lock();
+        TicketLockGuard {
+            lock: &self.lock,
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Tries to lock the TicketLocked and returns a guard that releases the lock when dropped.
+    #[inline]
+    pub fn try_lock(&self) -> Option<TicketLockGuard<'_, T>> {
+        if self.lock.try_lock() {
+            Some(TicketLockGuard {
+                lock: &self.lock,
+                value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                marker: core::marker::PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// A guard that releases the TicketLock when dropped.
+pub struct TicketLockGuard<'a, T: ?Sized> {
+    lock: &'a TicketLock,
+    value: NonNull<T>,
+    marker: core::marker::PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> core::ops::Deref for TicketLockGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> core::ops::DerefMut for TicketLockGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for TicketLockGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            self.lock.unlock();
+        }
+    }
+}
+
+/// A reader-writer lock, allowing either any number of readers or one writer to access a
+/// resource at a time.
+///
+/// While waiting, the core sleeps with WFE and is woken up by the SEV on unlock.
+/// At most 254 readers can hold the lock at the same time, further readers wait.
+pub struct RwSpinLock<T> {
+    state: AtomicU8,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send + Sync> Sync for RwSpinLock<T> {}
+
+impl<T> RwSpinLock<T> {
+    /// State of a RwSpinLock held by a writer, otherwise the state is the number of readers.
+    const WRITER: u8 = u8::MAX;
+
+    /// Creates a new RwSpinLock.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        RwSpinLock {
+            state: AtomicU8::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Waits until there is no writer, and returns a guard that releases the read lock when
+    /// dropped.
+    #[inline]
+    pub fn read(&self) -> RwSpinLockReadGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            wait_for_event();
+        }
+    }
+
+    /// Tries to lock the RwSpinLock for reading and returns a guard that releases the read lock
+    /// when dropped.
+    #[inline]
+    pub fn try_read(&self) -> Option<RwSpinLockReadGuard<'_, T>> {
+        self.state
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |readers| {
+                if readers < Self::WRITER - 1 {
+                    Some(readers + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| RwSpinLockReadGuard {
+                state: &self.state,
+                value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                marker: core::marker::PhantomData,
+            })
+    }
+
+    /// Waits until there are no readers and no writer, and returns a guard that releases the
+    /// write lock when dropped.
+    #[inline]
+    pub fn write(&self) -> RwSpinLockWriteGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            wait_for_event();
+        }
+    }
+
+    /// Tries to lock the RwSpinLock for writing and returns a guard that releases the write lock
+    /// when dropped.
+    #[inline]
+    pub fn try_write(&self) -> Option<RwSpinLockWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, Self::WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwSpinLockWriteGuard {
+                state: &self.state,
+                value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                marker: core::marker::PhantomData,
+            })
+    }
+}
+
+/// A guard that releases the read lock of a RwSpinLock when dropped.
+pub struct RwSpinLockReadGuard<'a, T: ?Sized> {
+    state: &'a AtomicU8,
+    value: NonNull<T>,
+    marker: core::marker::PhantomData<&'a T>,
+}
+
+impl<T: ?Sized> core::ops::Deref for RwSpinLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwSpinLockReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let _ = self
+            .state
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |readers| {
+                Some(readers - 1)
+            });
+        send_event();
+    }
+}
+
+/// A guard that releases the write lock of a RwSpinLock when dropped.
+pub struct RwSpinLockWriteGuard<'a, T: ?Sized> {
+    state: &'a AtomicU8,
+    value: NonNull<T>,
+    marker: core::marker::PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> core::ops::Deref for RwSpinLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> core::ops::DerefMut for RwSpinLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwSpinLockWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.state.store(0, Ordering::Release);
+        send_event();
+    }
+}
+
+/// A mutual exclusion primitive that masks interrupts in the current core while the lock is held.
+///
+/// Like [`interrupt::free`](crate::interrupt::free), the guard saves PRIMASK and disables
+/// interrupts, so an interrupt handler that contends for the lock can never preempt its holder on
+/// the same core. The lock is then acquired by spinning, which is only needed to exclude the
+/// other cores of a multi-core system. PRIMASK is restored when the guard is dropped, so the
+/// guards of a core must be dropped in the reverse order of their creation.
+pub struct IrqSpinLocked<T> {
+    lock: SpinLock,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for IrqSpinLocked<T> {}
+
+impl<T> IrqSpinLocked<T> {
+    /// Creates a new IrqSpinLocked.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        IrqSpinLocked {
+            lock: SpinLock::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Disables interrupts, locks the IrqSpinLocked and returns a guard that releases the lock and
+    /// restores PRIMASK when dropped.
+    #[inline]
+    pub fn lock_irqsave(&self) -> IrqSpinLockGuard<'_, T> {
+        let primask = interrupt::save();
+        self.lock.lock();
+        IrqSpinLockGuard {
+            lock: &self.lock,
+            primask,
+            // Safety: interrupts are disabled until the guard is dropped.
+            cs: unsafe { CriticalSection::new() },
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Disables interrupts and tries to lock the IrqSpinLocked, without spinning.
+    /// If the lock is held, PRIMASK is restored and `None` is returned.
+    #[inline]
+    pub fn try_lock_irqsave(&self) -> Option<IrqSpinLockGuard<'_, T>> {
+        let primask = interrupt::save();
+        if self.lock.try_lock() {
+            Some(IrqSpinLockGuard {
+                lock: &self.lock,
+                primask,
+                // Safety: interrupts are disabled until the guard is dropped.
+                cs: unsafe { CriticalSection::new() },
+                value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                marker: core::marker::PhantomData,
+            })
+        } else {
+            // Safety: `primask` was just saved.
+            unsafe { interrupt::restore(primask) };
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value. No locking is needed, as the lock is
+    /// borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// A guard that releases the IrqSpinLocked and restores PRIMASK when dropped.
+pub struct IrqSpinLockGuard<'a, T: ?Sized> {
+    lock: &'a SpinLock,
+    primask: Primask,
+    cs: CriticalSection,
+    value: NonNull<T>,
+    marker: core::marker::PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> IrqSpinLockGuard<'_, T> {
+    /// Returns a critical section token, valid while the guard is held, e.g. to borrow an
+    /// [`interrupt::Mutex`] without nesting [`interrupt::free`].
+    ///
+    /// # Safety
+    ///
+    /// - The guards must be dropped in the reverse order of their creation while the token is
+    ///   used: dropping an outer guard first enables interrupts again.
+    #[inline]
+    pub unsafe fn critical_section(&self) -> &CriticalSection {
+        &self.cs
+    }
+}
+
+impl<T: ?Sized> core::ops::Deref for IrqSpinLockGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> core::ops::DerefMut for IrqSpinLockGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for IrqSpinLockGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            self.lock.unlock();
+            // Safety: the PRIMASK was saved by the guard, the guards are dropped in reverse order.
+            interrupt::restore(self.primask);
+        }
+    }
+}
+
+/// A mutual exclusion primitive for sharing a resource between threads and interrupt handlers,
+/// based on the priority ceiling protocol.
+///
+/// While the resource is locked, all exceptions with a priority up to `CEILING` are masked, so
+/// none of the other users of the resource can preempt the holder of the lock. Unlike with a
+/// [`SpinLocked`], an interrupt handler can therefore never wait for a lock held by the code it
+/// interrupted.
+///
+/// `CEILING` is the raw priority value, as written to the NVIC and SCB priority registers, of the
+/// highest priority (numerically lowest) user of the resource. It must not be 0, which disables
+/// masking, including in the priority bits implemented by the device: the others read as zero.
+/// Locking panics if the ceiling doesn't raise BASEPRI, unless an enclosing lock already raised
+/// it. The resource must not be locked from a context with a higher priority than `CEILING`, as
+/// the lock doesn't mask that context. This is not checked.
+///
+/// The priority is raised with `BASEPRI_MAX`. ARMv6-M and ARMv8-M Baseline don't have BASEPRI,
+/// on these all interrupts are disabled with PRIMASK instead.
+///
+/// The lock only excludes the contexts of the current core, so the mutex is not `Sync` with the
+/// `multi-core` feature.
+///
+/// **IMPORTANT** If you are using a Cortex-M7 device with revision r0p1 you MUST enable the
+/// `cm7-r0p1` Cargo feature or this type WILL misbehave.
+pub struct PriorityCeilingMutex<T, const CEILING: u8> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+#[cfg(not(feature = "multi-core"))]
+unsafe impl<T: Send, const CEILING: u8> Sync for PriorityCeilingMutex<T, CEILING> {}
+
+impl<T, const CEILING: u8> PriorityCeilingMutex<T, CEILING> {
+    const CEILING_IS_VALID: () = assert!(CEILING != 0, "the priority ceiling must not be 0");
+
+    /// Creates a new PriorityCeilingMutex.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::CEILING_IS_VALID;
+
+        PriorityCeilingMutex {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Raises the priority to the ceiling and returns a guard that restores it when dropped.
+    ///
+    /// Panics if the resource is already locked, e.g. if it is locked again by the holder of
+    /// the lock.
+    #[inline]
+    pub fn lock(&self) -> PriorityCeilingGuard<'_, T> {
+        let mask = ceiling::raise(CEILING);
+
+        // With the priority raised, no other user of the resource can run.
+        if self.locked.load(Ordering::Relaxed) {
+            ceiling::restore(mask);
+            panic!("PriorityCeilingMutex is already locked");
+        }
+        self.locked.store(true, Ordering::Relaxed);
+
+        PriorityCeilingGuard {
+            locked: &self.locked,
+            mask,
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Returns a mutable reference to the value. No locking is needed, as the mutex is
+    /// borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the mutex and returns the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+/// A guard that unlocks the PriorityCeilingMutex and restores the previous priority when dropped.
+pub struct PriorityCeilingGuard<'a, T: ?Sized> {
+    locked: &'a AtomicBool,
+    mask: ceiling::Mask,
+    value: NonNull<T>,
+    marker: core::marker::PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> core::ops::Deref for PriorityCeilingGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> core::ops::DerefMut for PriorityCeilingGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for PriorityCeilingGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.locked.store(false, Ordering::Relaxed);
+        ceiling::restore(self.mask);
+    }
+}
+
+/// Raising and restoring the priority of the current context.
+mod ceiling {
+    #[cfg(all(cortex_m, not(armv6m), not(armv8m_base)))]
+    use crate::register::{basepri, basepri_max};
+    #[cfg(any(armv6m, armv8m_base))]
+    use crate::{interrupt, register::primask::Primask};
+    #[cfg(all(cortex_m, not(armv6m), not(armv8m_base)))]
+    use core::sync::atomic::{compiler_fence, Ordering};
+
+    /// The interrupt mask before the priority was raised: BASEPRI, or PRIMASK where there is
+    /// no BASEPRI.
+    #[cfg(not(any(armv6m, armv8m_base)))]
+    pub(super) type Mask = u8;
+    #[cfg(any(armv6m, armv8m_base))]
+    pub(super) type Mask = Primask;
+
+    /// Masks all exceptions with a priority up to `ceiling` and returns the previous mask.
+    #[inline]
+    pub(super) fn raise(ceiling: u8) -> Mask {
+        match () {
+            #[cfg(all(cortex_m, not(armv6m), not(armv8m_base)))]
+            () => {
+                let mask = basepri::read();
+                basepri_max::write(ceiling);
+                // A write of 0 in the implemented bits is ignored and would leave the interrupts unmasked.
+                assert!(
+                    basepri::read() != 0,
+                    "the priority ceiling has no implemented bit set and can't be masked with BASEPRI"
+                );
+                // Ensure no subsequent memory accesses are reordered to before the priority is raised.
+                compiler_fence(Ordering::SeqCst);
+                mask
+            }
+            #[cfg(any(armv6m, armv8m_base))]
+            () => {
+                let _ = ceiling;
+                interrupt::save()
+            }
+            #[cfg(not(cortex_m))]
+            () => {
+                let _ = ceiling;
+                panic!(
+                    "cortex_m::sync::PriorityCeilingMutex is only functional on cortex-m platforms"
+                );
+            }
+        }
+    }
+
+    /// Restores the mask returned by `raise`.
+    #[inline]
+    pub(super) fn restore(mask: Mask) {
+        match () {
+            #[cfg(all(cortex_m, not(armv6m), not(armv8m_base)))]
+            () => {
+                // Ensure no preceeding memory accesses are reordered to after the priority is restored.
+                compiler_fence(Ordering::SeqCst);
+                // Safety: the mask was read by `raise`, nested locks are released in reverse order.
+                unsafe { basepri::write(mask) }
+            }
+            #[cfg(any(armv6m, armv8m_base))]
+            () => {
+                // Safety: the mask was saved by `raise`, nested locks are released in reverse order.
+                unsafe { interrupt::restore(mask) }
+            }
+            #[cfg(not(cortex_m))]
+            () => {
+                let _ = mask;
+            }
+        }
+    }
+}
+
+/// A counting semaphore.
+///
+/// [`Semaphore::give`] never waits, so it can be called from interrupt handlers to signal
+/// threads waiting in [`Semaphore::take`].
+pub struct Semaphore {
+    count: AtomicU32,
+    max: u32,
+    wait: Wait,
+}
+
+impl Semaphore {
+    /// Creates a new Semaphore with `initial` permits, of at most `max`, busy-waiting in `take`.
+    #[inline]
+    pub const fn new(initial: u32, max: u32) -> Self {
+        Self::with_wait(initial, max, Wait::Spin)
+    }
+
+    /// Creates a new Semaphore with `initial` permits, of at most `max`, waiting in `take` as
+    /// specified by `wait`.
+    #[inline]
+    pub const fn with_wait(initial: u32, max: u32, wait: Wait) -> Self {
+        assert!(
+            initial <= max,
+            "the initial count exceeds the maximum count"
+        );
+        Semaphore {
+            count: AtomicU32::new(initial),
+            max,
+            wait,
+        }
+    }
+
+    /// Returns a permit to the Semaphore.
+    /// Returns `false` if the Semaphore already holds the maximum number of permits.
+    #[inline]
+    pub fn give(&self) -> bool {
+        let given = self
+            .count
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |count| {
+                (count < self.max).then_some(count + 1)
+            })
+            .is_ok();
+
+        if given {
+            self.wait.signal();
+        }
+        given
+    }
+
+    /// Tries to take a permit from the Semaphore.
+    /// Returns `true` if a permit was taken.
+    #[inline]
+    pub fn try_take(&self) -> bool {
+        self.count
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Waits until a permit is available and takes it.
+    #[inline]
+    pub fn take(&self) {
+        while !self.try_take() {
+            self.wait.wait();
+        }
+    }
+
+    /// Returns the number of available permits.
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/// A set of 32 event flags, which threads can wait for.
+///
+/// [`EventFlags::set`] and [`EventFlags::clear`] never wait, so they can be called from interrupt
+/// handlers to signal threads waiting in [`EventFlags::wait_any`] or [`EventFlags::wait_all`].
+pub struct EventFlags {
+    flags: AtomicU32,
+    wait: Wait,
+}
+
+impl EventFlags {
+    /// Creates a new EventFlags with all flags cleared, busy-waiting for flags.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_wait(Wait::Spin)
+    }
+
+    /// Creates a new EventFlags with all flags cleared, waiting for flags as specified by `wait`.
+    #[inline]
+    pub const fn with_wait(wait: Wait) -> Self {
+        EventFlags {
+            flags: AtomicU32::new(0),
+            wait,
+        }
+    }
+
+    /// Sets the flags in `mask` and returns the previous flags.
+    #[inline]
+    pub fn set(&self, mask: u32) -> u32 {
+        let flags = self.update(Ordering::Release, |flags| Some(flags | mask));
+        self.wait.signal();
+        flags
+    }
+
+    /// Clears the flags in `mask` and returns the previous flags.
+    #[inline]
+    pub fn clear(&self, mask: u32) -> u32 {
+        self.update(Ordering::Relaxed, |flags| Some(flags & !mask))
+    }
+
+    /// Returns the current flags.
+    #[inline]
+    pub fn get(&self) -> u32 {
+        self.flags.load(Ordering::Acquire)
+    }
+
+    /// Returns the flags if any flag in `mask` is set, and clears the flags in `mask` if `clear`
+    /// is `true`. Otherwise `None` is returned.
+    #[inline]
+    pub fn try_wait_any(&self, mask: u32, clear: bool) -> Option<u32> {
+        self.try_wait(mask, clear, |flags| flags & mask != 0)
+    }
+
+    /// Waits until any flag in `mask` is set and returns the flags. The flags in `mask` are
+    /// cleared if `clear` is `true`.
+    #[inline]
+    pub fn wait_any(&self, mask: u32, clear: bool) -> u32 {
+        loop {
+            if let Some(flags) = self.try_wait_any(mask, clear) {
+                return flags;
+            }
+            self.wait.wait();
+        }
+    }
+
+    /// Returns the flags if all flags in `mask` are set, and clears the flags in `mask` if `clear`
+    /// is `true`. Otherwise `None` is returned.
+    #[inline]
+    pub fn try_wait_all(&self, mask: u32, clear: bool) -> Option<u32> {
+        self.try_wait(mask, clear, |flags| flags & mask == mask)
+    }
+
+    /// Waits until all flags in `mask` are set and returns the flags. The flags in `mask` are
+    /// cleared if `clear` is `true`.
+    #[inline]
+    pub fn wait_all(&self, mask: u32, clear: bool) -> u32 {
+        loop {
+            if let Some(flags) = self.try_wait_all(mask, clear) {
+                return flags;
+            }
+            self.wait.wait();
+        }
+    }
+
+    /// Returns the flags if `ready` returns `true` for them, and clears the flags in `mask` if
+    /// `clear` is `true`, atomically.
+    #[inline]
+    fn try_wait<F>(&self, mask: u32, clear: bool, ready: F) -> Option<u32>
+    where
+        F: Fn(u32) -> bool,
+    {
+        self.flags
+            .fetch_update(Ordering::Acquire, Ordering::Acquire, |flags| {
+                if !ready(flags) {
+                    None
+                } else if clear {
+                    Some(flags & !mask)
+                } else {
+                    Some(flags)
+                }
+            })
+            .ok()
+    }
+
+    /// Applies `f` to the flags atomically and returns the previous flags.
+    #[inline]
+    fn update<F>(&self, order: Ordering, f: F) -> u32
+    where
+        F: FnMut(u32) -> Option<u32>,
+    {
+        match self.flags.fetch_update(order, Ordering::Relaxed, f) {
+            Ok(flags) | Err(flags) => flags,
+        }
+    }
+}
+
+impl Default for EventFlags {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A synchronization primitive that can be used to block a thread until a value is ready.
+/// The procedure is as follows:
+/// 1. The Caller calls step(NOT_READY) to indicate that it is about to start the initialization process.
+/// 2. The Caller initializes the value.
+/// 3. The Caller calls step(IN_TRANSIT) to indicate that the value is ready.
+/// If step 1 fails, the value is already being initialized and the Caller must wait until is() returns true.
+pub struct Ready {
+    ready: AtomicU8,
+}
+
+impl Ready {
+    const POISONED: u8 = 3;
+    const READY: u8 = 2;
+    const IN_TRANSIT: u8 = 1;
+    const NOT_READY: u8 = 0;
+
+    /// Initializes a new Ready.
+    pub const fn new() -> Self {
+        Self {
+            ready: AtomicU8::new(0),
+        }
+    }
+
+    /// Move the Ready to the next state, if it is in state `from`.
+    pub fn step(&self, from: u8) -> bool {
+        self.forward(from, from + 1)
+    }
+
+    /// Move the Ready to state `to` if it is in state `from`.
+    fn forward(&self, _from: u8, _to: u8) -> bool {
+        return self
+            .ready
+            .compare_exchange(_from, _to, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+    }
+
+    /// Move the Ready to state `to`, unconditionally.
+    /// Only the Caller that moved the Ready to IN_TRANSIT may finish the initialization process.
+    #[inline]
+    fn finish(&self, to: u8) {
+        self.ready.store(to, Ordering::Release);
+    }
+
+    /// Returns the current state.
+    #[inline]
+    fn state(&self) -> u8 {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Returns true if the value is ready.
+    pub fn is(&self) -> bool {
+        return self.ready.load(Ordering::Acquire) == Self::READY;
+    }
+}
+
+/// Error returned by [`OnceCell::get_or_try_init`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[allow(clippy::missing_inline_in_public_items)]
+pub enum InitError<E> {
+    /// The initialization function failed. The cell is now poisoned.
+    Failed(E),
+    /// A previous initialization panicked or failed.
+    Poisoned,
+}
+
+/// A synchronization primitive that represents a value that is initialized at most once.
+///
+/// If the initialization panics or fails, the cell is poisoned: it is never initialized, and
+/// waiting for it panics, until it is reset with [`OnceCell::take`].
+pub struct OnceCell<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    init: Ready,
+    wait: Wait,
+}
+
+/// Safety:
+/// 1. A `value` is only written to atomically and once.
+/// 2. A `value` is only readable from after the initialization process is finished.
+/// 3. A `init` is only written and read from atomically.
+unsafe impl<T> Sync for OnceCell<T> {}
+
+impl<T> OnceCell<T> {
+    /// Initializes a new OnceCell, busy-waiting for initializations in progress.
+    pub const fn new() -> Self {
+        Self::with_wait(Wait::Spin)
+    }
+
+    /// Initializes a new OnceCell, waiting for initializations in progress as specified by `wait`.
+    #[inline]
+    pub const fn with_wait(wait: Wait) -> Self {
+        Self {
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            init: Ready::new(),
+            wait,
+        }
+    }
+
+    /// Returns a reference to the value if it is initialized.
+    pub fn get(&self) -> Option<&T> {
+        if self.init.is() {
+            // Safety:
+            // 1. By contract, is the value initialized if init.is() returns true.
+            // 2. No writes are allowed to the value after the initialization process is finished.
+            Some(unsafe { self.get_unchecked() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if an initialization panicked or failed.
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.init.state() == Ready::POISONED
+    }
+
+    /// Sets the value if it is not already initialized, and returns a reference to the value.
+    ///
+    /// Panics if the cell is poisoned.
+    #[inline]
+    pub fn set_or_get(&self, value: T) -> &T {
+        match self.set(value) {
+            Some(value) => value,
+            None => self.wait_ready().expect("OnceCell is poisoned"),
+        }
+    }
+
+    /// Initializes the value with `f` if it is not already initialized, and returns a reference
+    /// to the value. `f` is only called if the cell is uninitialized.
+    ///
+    /// Panics if the cell is poisoned.
+    #[inline]
+    pub fn do_or_get<F>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        match self.get_or_try_init(|| Ok::<T, Infallible>(f())) {
+            Ok(value) => value,
+            Err(InitError::Failed(never)) => match never {},
+            Err(InitError::Poisoned) => panic!("OnceCell is poisoned"),
+        }
+    }
+
+    /// Initializes the value with `f` if it is not already initialized, and returns a reference
+    /// to the value. `f` is only called if the cell is uninitialized.
+    ///
+    /// If `f` fails, its error is returned and the cell is poisoned. If `f` panics, the cell is
+    /// poisoned. If the cell is poisoned, `InitError::Poisoned` is returned.
+    #[inline]
+    pub fn get_or_try_init<F, E>(&self, f: F) -> Result<&T, InitError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+
+        if !self.init.step(Ready::NOT_READY) {
+            // Initialization is already in progress, or finished.
+            return self.wait_ready().ok_or(InitError::Poisoned);
+        }
+
+        /// Poisons the cell and wakes up the waiters if `f` unwinds.
+        struct Poison<'a>(&'a Ready, &'a Wait);
+
+        impl Drop for Poison<'_> {
+            fn drop(&mut self) {
+                self.0.finish(Ready::POISONED);
+                self.1.signal();
+            }
+        }
+
+        let poison = Poison(&self.init, &self.wait);
+        let result = f();
+        core::mem::forget(poison);
+
+        match result {
+            Ok(value) => Ok(self.complete(value)),
+            Err(err) => {
+                self.init.finish(Ready::POISONED);
+                self.wait.signal();
+                Err(InitError::Failed(err))
+            }
+        }
+    }
+
+    /// Sets the value if it is not already initialized, returns a reference to the value if it was not set previously.
+    #[inline]
+    pub fn set(&self, value: T) -> Option<&T> {
+        if self.init.step(Ready::NOT_READY) {
+            Some(self.complete(value))
+        } else {
+            None
+        }
+    }
+
+    /// Takes the value out of the cell, leaving it uninitialized. A poisoned cell is reset and
+    /// `None` is returned.
+    #[inline]
+    pub fn take(&mut self) -> Option<T> {
+        let ready = self.init.is();
+        self.init.finish(Ready::NOT_READY);
+
+        if ready {
+            // Safety: the value was initialized, and the cell no longer is.
+            Some(unsafe { self.value.get().read().assume_init() })
+        } else {
+            None
+        }
+    }
+
+    /// Resets the cell to uninitialized through a shared reference, e.g. in a `static`, and
+    /// returns the value if it was initialized. A poisoned cell is reset and `None` is returned.
+    /// An initialization in progress is waited for.
+    ///
+    /// # Safety
+    ///
+    /// No reference to the value, as returned by the other methods, may be alive.
+    #[inline]
+    pub unsafe fn reset(&self) -> Option<T> {
+        loop {
+            match self.init.state() {
+                Ready::IN_TRANSIT => self.wait.wait(),
+                Ready::READY => {
+                    // Claim the value, so that it is only moved out once.
+                    if self.init.forward(Ready::READY, Ready::IN_TRANSIT) {
+                        // Safety: the value is initialized, and by contract no longer referenced.
+                        let value = self.value.get().read().assume_init();
+                        self.init.finish(Ready::NOT_READY);
+                        self.wait.signal();
+                        return Some(value);
+                    }
+                }
+                Ready::POISONED => {
+                    if self.init.forward(Ready::POISONED, Ready::NOT_READY) {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Consumes the cell and returns the value, if it is initialized.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    /// Writes the value and finishes the initialization process started by the caller.
+    #[inline]
+    fn complete(&self, value: T) -> &T {
+        // Safety: We are in the IN_TRANSIT state, so we are the only ones that can write to the value.
+        // We are also the only ones that can read from the value.
+        unsafe {
+            self.value.get().write(MaybeUninit::new(value));
+        }
+
+        self.init.finish(Ready::READY);
+        self.wait.signal();
+
+        // Safety: We are now in the READY state, so no writes can happen to the value.
+        // 1. It is safe to create a immutable reference to the value.
+        // 2. We initialized the value, so it is safe to return a reference to it.
+        unsafe { self.get_unchecked() }
+    }
+
+    /// Waits until the initialization process in progress finished, and returns a reference to
+    /// the value, or `None` if the cell is poisoned.
+    #[inline]
+    fn wait_ready(&self) -> Option<&T> {
+        loop {
+            match self.init.state() {
+                Ready::IN_TRANSIT => self.wait.wait(),
+                // Safety: By contract, is the value initialized in the READY state.
+                Ready::READY => return Some(unsafe { self.get_unchecked() }),
+                // POISONED, the initialization process was started, so the cell isn't NOT_READY.
+                _ => return None,
+            }
+        }
+    }
+
+    /// Returns a reference to the value, unchecked.
+    ///
+    /// # Safety
+    /// Preconditions: The value must be initialized.
+    /// Postconditions: The value is returned.
+    unsafe fn get_unchecked(&self) -> &T {
+        unsafe { (&*self.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    #[inline]
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+/// A value that is initialized on first access, with the function passed to [`Lazy::new`].
+///
+/// Unlike a [`OnceCell`], a `Lazy` knows how to initialize itself, so it can be dereferenced
+/// directly, e.g. in a `static`:
+///
+/// ```
+/// use cortex_m::sync::Lazy;
+///
+/// static TABLE: Lazy<[u32; 4]> = Lazy::new(|| core::array::from_fn(|i| 1 << i));
+///
+/// assert_eq!(TABLE[3], 8);
+/// ```
+///
+/// If the initialization panics, the `Lazy` is poisoned and all further accesses panic.
+pub struct Lazy<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: UnsafeCell<Option<F>>,
+}
+
+/// Safety: `init` is only accessed by the context that initializes the cell, see `Lazy::force`.
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+impl<T, F> Lazy<T, F> {
+    /// Creates a new Lazy, initialized with `init` on first access.
+    #[inline]
+    pub const fn new(init: F) -> Self {
+        Lazy {
+            cell: OnceCell::new(),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Initializes the value if it is not already initialized, and returns a reference to it.
+    ///
+    /// Panics if the `Lazy` is poisoned.
+    #[inline]
+    pub fn force(this: &Self) -> &T {
+        this.cell.do_or_get(|| {
+            // Safety: only the context that initializes the cell runs this closure, and only once.
+            let init = unsafe { (*this.init.get()).take() };
+            match init {
+                Some(init) => init(),
+                None => unreachable!("Lazy is initialized at most once"),
+            }
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> core::ops::Deref for Lazy<T, F> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
+
+/// Waits for an event, see [`asm::wfe`]. Natively, this is a spin loop hint.
+#[inline]
+fn wait_for_event() {
+    match () {
+        #[cfg(cortex_m)]
+        () => asm::wfe(),
+        #[cfg(not(cortex_m))]
+        () => core::hint::spin_loop(),
+    }
+}
+
+/// Wakes up the cores waiting for an event, after the preceding memory accesses completed.
+#[inline]
+fn send_event() {
+    asm::dsb();
+    #[cfg(cortex_m)]
+    asm::sev();
+}
@@ -0,0 +1,243 @@
+//! Single-producer single-consumer queue
+//!
+//! A [`Queue`] is split into a [`Producer`] and a [`Consumer`], which can be moved to different
+//! contexts, e.g. an interrupt handler that enqueues received bytes and a thread that dequeues
+//! them. Neither side ever waits or masks interrupts for longer than an atomic access.
+//!
+//! The producer only writes the tail index and the consumer only writes the head index. An
+//! element is written before the tail is published with `Release` and read after the tail is
+//! observed with `Acquire`, and the same holds for freeing slots through the head. Without the
+//! `atomic-cas` feature the indices are emulated atomics, whose critical sections order the
+//! accesses on single-core systems.
+//!
+//! ```
+//! use cortex_m::sync::spsc::Queue;
+//!
+//! let mut queue: Queue<u8, 4> = Queue::new();
+//! let (mut producer, mut consumer) = queue.split();
+//!
+//! assert!(producer.enqueue(1).is_ok());
+//! assert_eq!(consumer.dequeue(), Some(1));
+//! assert_eq!(consumer.dequeue(), None);
+//! ```
+
+use crate::atomic::{AtomicU32, Ordering};
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+
+/// A queue of at most `N` elements, with one producer and one consumer.
+pub struct Queue<T, const N: usize> {
+    /// The index of the next element to dequeue, written by the consumer.
+    head: AtomicU32,
+    /// The index of the next element to enqueue, written by the producer.
+    tail: AtomicU32,
+    buffer: UnsafeCell<MaybeUninit<[T; N]>>,
+}
+
+/// Safety: the elements are only accessed through the `Producer` and `Consumer`, of which
+/// there is at most one each, and each slot is owned by only one of them at a time.
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+impl<T, const N: usize> Queue<T, N> {
+    const CAPACITY_IS_VALID: () = assert!(
+        N > 0 && N <= (u32::MAX / 2) as usize,
+        "the capacity must be between 1 and u32::MAX / 2"
+    );
+
+    /// The indices wrap at twice the capacity, to tell a full queue from an empty one.
+    const WRAP: u32 = 2 * N as u32;
+
+    /// Creates a new, empty Queue.
+    #[inline]
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::CAPACITY_IS_VALID;
+
+        Queue {
+            head: AtomicU32::new(0),
+            tail: AtomicU32::new(0),
+            buffer: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns the maximum number of elements in the queue.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Splits the queue into its producer and consumer.
+    #[inline]
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (
+            Producer {
+                queue: self,
+                marker: PhantomData,
+            },
+            Consumer {
+                queue: self,
+                marker: PhantomData,
+            },
+        )
+    }
+
+    /// Returns the number of elements between the indices `head` and `tail`.
+    #[inline]
+    fn len_between(head: u32, tail: u32) -> usize {
+        if tail >= head {
+            (tail - head) as usize
+        } else {
+            (Self::WRAP - head + tail) as usize
+        }
+    }
+
+    /// Returns the index following `index`.
+    #[inline]
+    fn next(index: u32) -> u32 {
+        (index + 1) % Self::WRAP
+    }
+
+    /// Returns a pointer to the slot of `index`.
+    #[inline]
+    fn slot(&self, index: u32) -> *mut T {
+        // Safety: the slot is within the buffer.
+        unsafe { (self.buffer.get() as *mut T).add(index as usize % N) }
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    #[inline]
+    fn drop(&mut self) {
+        let mut head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Relaxed);
+        while head != tail {
+            // Safety: the slots between head and tail are initialized.
+            unsafe { self.slot(head).drop_in_place() };
+            head = Self::next(head);
+        }
+    }
+}
+
+/// The enqueuing side of a [`Queue`].
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+    marker: PhantomData<*const ()>,
+}
+
+/// Safety: the producer can be moved to another context, together with the elements it enqueues.
+unsafe impl<T: Send, const N: usize> Send for Producer<'_, T, N> {}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Adds `value` to the back of the queue.
+    /// Returns `Err(value)` if the queue is full.
+    #[inline]
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        let queue = self.queue;
+        // Only the producer writes the tail.
+        let tail = queue.tail.load(Ordering::Relaxed);
+        // Synchronizes with the consumer freeing the slot.
+        let head = queue.head.load(Ordering::Acquire);
+
+        if Queue::<T, N>::len_between(head, tail) == N {
+            return Err(value);
+        }
+
+        // Safety: the slot is free and owned by the producer until the tail is published.
+        unsafe { queue.slot(tail).write(value) };
+        queue
+            .tail
+            .store(Queue::<T, N>::next(tail), Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns `true` if the queue is full.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Returns the number of elements in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        let head = self.queue.head.load(Ordering::Acquire);
+        Queue::<T, N>::len_between(head, tail)
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The dequeuing side of a [`Queue`].
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+    marker: PhantomData<*const ()>,
+}
+
+/// Safety: the consumer can be moved to another context, together with the elements it dequeues.
+unsafe impl<T: Send, const N: usize> Send for Consumer<'_, T, N> {}
+
+impl<T, const N: usize> Consumer<'_, T, N> {
+    /// Removes the element at the front of the queue.
+    /// Returns `None` if the queue is empty.
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        let queue = self.queue;
+        // Only the consumer writes the head.
+        let head = queue.head.load(Ordering::Relaxed);
+        // Synchronizes with the producer publishing the element.
+        let tail = queue.tail.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        // Safety: the slot is initialized and owned by the consumer until the head is published.
+        let value = unsafe { queue.slot(head).read() };
+        queue
+            .head
+            .store(Queue::<T, N>::next(head), Ordering::Release);
+        Some(value)
+    }
+
+    /// Returns a reference to the element at the front of the queue, without removing it.
+    #[inline]
+    pub fn peek(&self) -> Option<&T> {
+        let queue = self.queue;
+        let head = queue.head.load(Ordering::Relaxed);
+        let tail = queue.tail.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        // Safety: the slot is initialized, and only freed by `dequeue`, which borrows the
+        // consumer mutably.
+        Some(unsafe { &*queue.slot(head) })
+    }
+
+    /// Returns the number of elements in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        let tail = self.queue.tail.load(Ordering::Acquire);
+        Queue::<T, N>::len_between(head, tail)
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,179 @@
+use super::spsc::Queue;
+use super::{EventFlags, InitError, OnceCell, RwSpinLock, Semaphore, TicketLock, TicketLocked};
+use core::sync::atomic::Ordering;
+
+#[test]
+fn ticket_lock_try_lock() {
+    let lock = TicketLock::new();
+    assert!(lock.try_lock());
+    assert!(!lock.try_lock());
+
+    unsafe { lock.unlock() };
+    assert!(lock.try_lock());
+    unsafe { lock.unlock() };
+}
+
+#[test]
+fn ticket_lock_order() {
+    let locked = TicketLocked::new(std::vec::Vec::new());
+    let guard = locked.lock();
+
+    std::thread::scope(|scope| {
+        // Start the waiters one after the other, each once the previous one drew its ticket.
+        for id in 1..=3 {
+            let locked = &locked;
+            scope.spawn(move || locked.lock().push(id));
+            while locked.lock.next.load(Ordering::Relaxed) != id + 1 {
+                std::thread::yield_now();
+            }
+        }
+        drop(guard);
+    });
+
+    assert_eq!(*locked.lock(), [1, 2, 3]);
+    assert!(locked.try_lock().is_some());
+}
+
+#[test]
+fn rw_spin_lock_readers() {
+    let lock = RwSpinLock::new(7);
+    let readers: [_; 254] = core::array::from_fn(|_| lock.try_read().unwrap());
+    assert!(readers.iter().all(|reader| **reader == 7));
+    assert!(lock.try_read().is_none());
+    assert!(lock.try_write().is_none());
+
+    drop(readers);
+    let mut writer = lock.try_write().unwrap();
+    *writer = 8;
+    assert!(lock.try_read().is_none());
+    assert!(lock.try_write().is_none());
+
+    drop(writer);
+    assert_eq!(*lock.read(), 8);
+}
+
+#[test]
+fn spsc_queue() {
+    let mut queue: Queue<u32, 3> = Queue::new();
+    let (mut producer, mut consumer) = queue.split();
+
+    // Wrap the indices around a few times.
+    for round in 0..5 {
+        for i in 0..3 {
+            assert!(producer.enqueue(round * 3 + i).is_ok());
+        }
+        assert!(producer.is_full());
+        assert_eq!(producer.enqueue(99), Err(99));
+        assert_eq!(consumer.len(), 3);
+
+        assert_eq!(consumer.peek(), Some(&(round * 3)));
+        for i in 0..3 {
+            assert_eq!(consumer.dequeue(), Some(round * 3 + i));
+        }
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.dequeue(), None);
+    }
+}
+
+#[test]
+fn spsc_queue_drop() {
+    use std::rc::Rc;
+
+    let value = Rc::new(());
+    {
+        let mut queue: Queue<Rc<()>, 4> = Queue::new();
+        let (mut producer, mut consumer) = queue.split();
+        for _ in 0..4 {
+            assert!(producer.enqueue(value.clone()).is_ok());
+        }
+        drop(consumer.dequeue());
+        assert_eq!(Rc::strong_count(&value), 4);
+    }
+    assert_eq!(Rc::strong_count(&value), 1);
+}
+
+#[test]
+fn semaphore() {
+    let semaphore = Semaphore::new(1, 2);
+    assert!(semaphore.try_take());
+    assert!(!semaphore.try_take());
+
+    assert!(semaphore.give());
+    assert!(semaphore.give());
+    assert!(!semaphore.give());
+    assert_eq!(semaphore.count(), 2);
+
+    semaphore.take();
+    assert_eq!(semaphore.count(), 1);
+}
+
+#[test]
+fn event_flags() {
+    let flags = EventFlags::new();
+    assert_eq!(flags.set(0b0101), 0);
+    assert_eq!(flags.try_wait_all(0b0111, true), None);
+    assert_eq!(flags.try_wait_any(0b0110, false), Some(0b0101));
+    assert_eq!(flags.wait_all(0b0101, true), 0b0101);
+    assert_eq!(flags.get(), 0);
+
+    flags.set(0b1000);
+    assert_eq!(flags.clear(0b1001), 0b1000);
+    assert_eq!(flags.try_wait_any(u32::MAX, false), None);
+}
+
+#[test]
+fn once_cell_init() {
+    let cell = OnceCell::new();
+    assert_eq!(*cell.do_or_get(|| 1), 1);
+    assert_eq!(*cell.do_or_get(|| panic!("initialized twice")), 1);
+    assert_eq!(cell.set(2), None);
+    assert_eq!(cell.get_or_try_init(|| Err::<_, ()>(())), Ok(&1));
+}
+
+#[test]
+fn once_cell_poisoned() {
+    let mut cell: OnceCell<u32> = OnceCell::new();
+    assert_eq!(
+        cell.get_or_try_init(|| Err("failed")),
+        Err(InitError::Failed("failed"))
+    );
+    assert!(cell.is_poisoned());
+    assert_eq!(
+        cell.get_or_try_init(|| Ok::<_, ()>(1)),
+        Err(InitError::Poisoned)
+    );
+    assert_eq!(cell.get(), None);
+
+    // Taking the value resets the cell.
+    assert_eq!(cell.take(), None);
+    assert_eq!(*cell.do_or_get(|| 2), 2);
+    assert_eq!(cell.take(), Some(2));
+    assert_eq!(cell.get(), None);
+
+    // A panicking initialization poisons the cell.
+    let cell: OnceCell<u32> = OnceCell::new();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        cell.do_or_get(|| panic!("initialization failed"));
+    }));
+    assert!(result.is_err());
+    assert!(cell.is_poisoned());
+    assert_eq!(cell.into_inner(), None);
+}
+
+#[test]
+fn once_cell_reset() {
+    static CELL: OnceCell<u32> = OnceCell::new();
+    assert_eq!(unsafe { CELL.reset() }, None);
+    assert_eq!(*CELL.do_or_get(|| 1), 1);
+    assert_eq!(unsafe { CELL.reset() }, Some(1));
+    assert_eq!(CELL.get(), None);
+
+    // A poisoned cell can be initialized again after a reset.
+    assert_eq!(
+        CELL.get_or_try_init(|| Err("failed")),
+        Err(InitError::Failed("failed"))
+    );
+    assert_eq!(unsafe { CELL.reset() }, None);
+    assert!(!CELL.is_poisoned());
+    assert_eq!(*CELL.do_or_get(|| 2), 2);
+}
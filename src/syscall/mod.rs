@@ -0,0 +1,138 @@
+//! System calls
+//!
+//! Threads issue system calls with the [`syscall!`](crate::syscall!) macro, which executes
+//! `svc #num` with the arguments in r0-r3. The kernel registers a table of [`Handler`]s with
+//! [`set_handlers`], indexed by the SVC number. [`dispatch`] decodes the SVC number from the
+//! stacked PC, calls the handler with the stacked r0-r3 and writes its results to the stacked
+//! r0 and r1, from where the caller of the system call receives them on exception return.
+//!
+//! With the `syscall-svc` feature enabled, this module provides the `SVCall` exception handler,
+//! which selects the stack the caller's frame is on, MSP or PSP, from EXC_RETURN (like
+//! [`irq_is_msp!`](crate::irq_is_msp!)) and passes it to [`dispatch`].
+
+#[cfg(all(cortex_m, feature = "syscall-svc"))]
+use core::arch::global_asm;
+
+use crate::sync::OnceCell;
+
+#[cfg(test)]
+mod test;
+
+/// A system call handler.
+/// Receives the arguments r0-r3 of the system call and returns the results r0 and r1.
+pub type Handler = fn(args: [u32; 4]) -> (u32, u32);
+
+/// Value of r0 returned for system call numbers without a handler.
+pub const INVALID: u32 = u32::MAX;
+
+// Layout of the hardware-stacked frame, in words from the bottom of the frame.
+const FRAME_R0: usize = 0;
+const FRAME_R1: usize = 1;
+const FRAME_PC: usize = 6;
+
+/// The system call handlers, indexed by SVC number.
+static HANDLERS: OnceCell<&'static [Handler]> = OnceCell::new();
+
+/// Registers the system call handlers. The handler of SVC number `n` is `handlers[n]`.
+///
+/// Returns `false` if handlers were already registered.
+#[inline]
+pub fn set_handlers(handlers: &'static [Handler]) -> bool {
+    HANDLERS.set(handlers).is_some()
+}
+
+/// Returns the SVC number of the system call that stacked `frame`.
+///
+/// # Safety
+///
+/// `frame` must point to the frame stacked on entry of the SVCall exception.
+#[inline]
+pub unsafe fn svc_number(frame: *const u32) -> u8 {
+    svc_imm(frame.add(FRAME_PC).read() as usize as *const u8)
+}
+
+/// Returns the immediate of the `svc` instruction before `pc`.
+///
+/// # Safety
+///
+/// `pc` must point to the instruction after a 16-bit `svc #imm8` instruction.
+unsafe fn svc_imm(pc: *const u8) -> u8 {
+    // The immediate is the low byte of the little-endian instruction.
+    pc.sub(2).read()
+}
+
+/// Dispatches the system call that stacked `frame` to the registered handler.
+///
+/// If no handler is registered for the SVC number, r0 is set to [`INVALID`].
+///
+/// # Safety
+///
+/// `frame` must point to the frame stacked on entry of the SVCall exception, and the frame
+/// must be restored on exception return.
+#[inline]
+pub unsafe extern "C" fn dispatch(frame: *mut u32) {
+    call(frame, svc_number(frame));
+}
+
+/// Calls the handler of system call `num` with the stacked r0-r3 and writes its results to the
+/// stacked r0 and r1.
+///
+/// # Safety
+///
+/// `frame` must point to a stacked exception frame.
+unsafe fn call(frame: *mut u32, num: u8) {
+    let handler = HANDLERS
+        .get()
+        .and_then(|handlers| handlers.get(num as usize).copied());
+
+    let (r0, r1) = match handler {
+        Some(handler) => handler(core::array::from_fn(|i| frame.add(FRAME_R0 + i).read())),
+        None => (INVALID, frame.add(FRAME_R1).read()),
+    };
+
+    frame.add(FRAME_R0).write(r0);
+    frame.add(FRAME_R1).write(r1);
+}
+
+// ARMv7-M and ARMv8-M Mainline.
+// `dispatch` is tail-called, so it returns from the exception with the EXC_RETURN value in lr.
+#[cfg(all(any(armv7m, armv8m_main), feature = "syscall-svc"))]
+global_asm!(
+    ".section .text.SVCall, \"ax\", %progbits",
+    ".global SVCall",
+    ".type SVCall, %function",
+    ".thumb_func",
+    "SVCall:",
+    "    tst lr, #4",
+    "    ite eq",
+    "    mrseq r0, msp",
+    "    mrsne r0, psp",
+    "    b {dispatch}",
+    ".size SVCall, . - SVCall",
+    dispatch = sym dispatch,
+);
+
+// ARMv6-M and ARMv8-M Baseline.
+// The branch range is too short to reach `dispatch` directly, so its address is loaded instead.
+#[cfg(all(any(armv6m, armv8m_base), feature = "syscall-svc"))]
+global_asm!(
+    ".section .text.SVCall, \"ax\", %progbits",
+    ".global SVCall",
+    ".type SVCall, %function",
+    ".thumb_func",
+    "SVCall:",
+    "    mov r0, lr",
+    "    movs r1, #4",
+    "    tst r0, r1",
+    "    bne 1f",
+    "    mrs r0, msp",
+    "    b 2f",
+    "1:",
+    "    mrs r0, psp",
+    "2:",
+    "    ldr r1, ={dispatch}",
+    "    bx r1",
+    ".ltorg",
+    ".size SVCall, . - SVCall",
+    dispatch = sym dispatch,
+);
@@ -0,0 +1,32 @@
+use super::{call, set_handlers, svc_imm, Handler, INVALID};
+
+// The stacked PC of a host frame can't hold a host address, so the SVC number is decoded and
+// the handler called separately.
+
+#[test]
+fn svc_number() {
+    // `svc #0x2a` followed by a `nop`.
+    let code: [u8; 4] = [0x2a, 0xdf, 0x00, 0xbf];
+    assert_eq!(unsafe { svc_imm(code.as_ptr().add(2)) }, 0x2a);
+}
+
+#[test]
+fn dispatch() {
+    fn sum(args: [u32; 4]) -> (u32, u32) {
+        (args.iter().sum(), args[0])
+    }
+
+    static HANDLERS: [Handler; 2] = [|_| (0, 0), sum];
+    assert!(set_handlers(&HANDLERS));
+    assert!(!set_handlers(&HANDLERS));
+
+    // r0-r3, r12, lr, pc and xPSR.
+    let mut frame = [1, 2, 3, 4, 5, 6, 7, 8];
+    unsafe { call(frame.as_mut_ptr(), 1) };
+    assert_eq!(frame, [10, 1, 3, 4, 5, 6, 7, 8]);
+
+    // Without a handler r0 is INVALID and r1 is left unchanged.
+    let mut frame = [1, 2, 3, 4, 5, 6, 7, 8];
+    unsafe { call(frame.as_mut_ptr(), 2) };
+    assert_eq!(frame, [INVALID, 2, 3, 4, 5, 6, 7, 8]);
+}
@@ -1 +1,70 @@
-//! Dispatcher service.
+//! Thread dispatcher service.
+//!
+//! The kernel scheduler implements [`Dispatcher`] and registers it with [`register`].
+//! The HAL then drives it: [`systick`] is called from the SysTick handler to account time slices,
+//! and [`dispatch`] is called from the PendSV handler to select the thread to switch to.
+//! With the `sched-pendsv` feature enabled, [`register`] installs [`dispatch`] as scheduler of the
+//! provided PendSV handler.
+
+use super::SchedCtx;
+use crate::sched::{self, CtxPtr};
+use crate::sync::OnceCell;
+
+/// Trait for the kernel scheduler.
+pub trait Dispatcher: Sync {
+    /// Selects the thread to run next.
+    ///
+    /// `current` is the context of the interrupted thread, or 0 if no thread was running.
+    /// The returned context is resumed and must have been created by
+    /// `sched::ThreadContext::from_empty` or saved by the PendSV handler.
+    fn select(&self, current: SchedCtx) -> SchedCtx;
+
+    /// Accounts a tick of the time slice of the running thread.
+    ///
+    /// Returns `true` if the running thread should be preempted.
+    fn tick(&self) -> bool;
+}
+
+/// The registered dispatcher.
+static DISPATCHER: OnceCell<&'static dyn Dispatcher> = OnceCell::new();
+
+/// Registers the dispatcher driven by [`systick`] and [`dispatch`].
+///
+/// Returns `false` if a dispatcher was already registered, or if another scheduler was already
+/// registered with the PendSV handler, in which case [`dispatch`] is never called by the handler.
+#[inline]
+pub fn register(dispatcher: &'static dyn Dispatcher) -> bool {
+    if DISPATCHER.set(dispatcher).is_none() {
+        return false;
+    }
+
+    match () {
+        #[cfg(feature = "sched-pendsv")]
+        () => sched::pendsv::set_scheduler(dispatch),
+        #[cfg(not(feature = "sched-pendsv"))]
+        () => true,
+    }
+}
+
+/// Accounts a tick of the time slice of the running thread, and reschedules if the dispatcher
+/// requests it. To be called from the SysTick handler.
+#[inline]
+pub fn systick() {
+    if let Some(dispatcher) = DISPATCHER.get() {
+        if dispatcher.tick() {
+            sched::reschedule();
+        }
+    }
+}
+
+/// Selects the thread to switch to. To be called from the PendSV handler, after the context
+/// of the interrupted thread has been saved.
+///
+/// Without a registered dispatcher, the interrupted thread is resumed.
+#[inline]
+pub extern "C" fn dispatch(current: CtxPtr) -> CtxPtr {
+    match DISPATCHER.get() {
+        Some(dispatcher) => dispatcher.select(current as SchedCtx) as CtxPtr,
+        None => current,
+    }
+}
@@ -3,5 +3,5 @@
 /// Thread dispatcher service.
 pub mod dispatcher;
 
-/// The return type of the sched_call.
-pub type SchedCtx = u32;
+/// The address of a thread context, as exchanged with the [`dispatcher::Dispatcher`].
+pub type SchedCtx = usize;